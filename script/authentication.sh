@@ -0,0 +1 @@
+http PUT localhost:4000/admin/authentication acr=2 amr:='["pwd", "otp", "mfa"]'
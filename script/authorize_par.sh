@@ -0,0 +1,3 @@
+http localhost:4000/authorize \
+client_id==0jrabyQWm4B9zVJPbotY \
+request_uri==$1
@@ -0,0 +1,6 @@
+http -f POST localhost:4000/bc-authorize \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+scope="openid email profile" \
+login_hint=johnny \
+binding_message=$1
@@ -0,0 +1 @@
+http -f POST localhost:4000/admin/ciba auth_req_id=$1 action=${2:-approve}
@@ -0,0 +1,4 @@
+http -f POST localhost:4000/device_authorization \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+scope="email openid profile"
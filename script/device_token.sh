@@ -0,0 +1,5 @@
+http -f POST localhost:4000/token \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+device_code=$1 \
+grant_type=urn:ietf:params:oauth:grant-type:device_code
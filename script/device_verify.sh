@@ -0,0 +1,3 @@
+http -f POST localhost:4000/device \
+user_code=$1 \
+action=$2
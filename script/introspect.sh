@@ -0,0 +1,4 @@
+http -f POST localhost:4000/introspect \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+token=$1
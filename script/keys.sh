@@ -0,0 +1 @@
+http localhost:4000/keys
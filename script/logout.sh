@@ -0,0 +1,4 @@
+http -f POST localhost:4000/logout \
+id_token_hint=$1 \
+post_logout_redirect_uri=http://localhost:3000/logout \
+state=12345678901234567890
@@ -0,0 +1 @@
+http localhost:4000/admin/logout/deliveries
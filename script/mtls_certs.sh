@@ -0,0 +1,14 @@
+mkdir -p certs
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -days 365 \
+-subj "/CN=localhost" -addext "subjectAltName=DNS:localhost" \
+-keyout certs/server.key -out certs/server.pem
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -days 365 \
+-subj "/O=Example/CN=Example CA" \
+-keyout certs/ca.key -out certs/ca.pem
+openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+-subj "/O=Example/CN=client.example.com" \
+-keyout certs/client.key -out certs/client.csr
+printf "extendedKeyUsage=clientAuth\n" > certs/client.ext
+openssl x509 -req -days 365 -CA certs/ca.pem -CAkey certs/ca.key -CAcreateserial \
+-extfile certs/client.ext \
+-in certs/client.csr -out certs/client.pem
@@ -0,0 +1,10 @@
+http -f POST localhost:4000/par \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+code_challenge_method=S256 \
+code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM \
+redirect_uri=http://localhost:3000/success \
+response_mode=form_post \
+response_type=code \
+scope="address email openid phone profile" \
+state=12345678901234567890
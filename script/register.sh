@@ -0,0 +1,4 @@
+http POST localhost:4000/register \
+redirect_uris:='["http://localhost:3000/success"]' \
+grant_types:='["authorization_code", "password"]' \
+token_endpoint_auth_method=client_secret_post
@@ -0,0 +1,2 @@
+http localhost:4000/register/$1 \
+"Authorization: Bearer $2"
@@ -0,0 +1 @@
+http POST localhost:4000/admin/keys/rotate
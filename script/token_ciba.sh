@@ -0,0 +1,5 @@
+http -f POST localhost:4000/token \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+grant_type=urn:openid:params:grant-type:ciba \
+auth_req_id=$1
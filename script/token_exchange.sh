@@ -0,0 +1,9 @@
+http -f POST localhost:4000/token \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+grant_type=urn:ietf:params:oauth:grant-type:token-exchange \
+subject_token=$1 \
+subject_token_type=urn:ietf:params:oauth:token-type:access_token \
+actor_token=$1 \
+actor_token_type=urn:ietf:params:oauth:token-type:access_token \
+audience=$2
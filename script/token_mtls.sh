@@ -0,0 +1,6 @@
+http --verify=no --cert=certs/client.pem --cert-key=certs/client.key -f POST https://localhost:4443/token \
+client_id=$1 \
+code_verifier=dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk \
+code=$2 \
+grant_type=authorization_code \
+redirect_uri=http://localhost:3000/success
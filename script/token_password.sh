@@ -0,0 +1,7 @@
+http -f POST localhost:4000/token \
+client_id=0jrabyQWm4B9zVJPbotY \
+client_secret=6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc \
+grant_type=password \
+username=johnny \
+password=$1 \
+scope="openid email profile"
@@ -0,0 +1,136 @@
+use crate::authorize::extractor::AuthorizeParams;
+use crate::authorize::handler;
+use crate::bounded_string::SecureString;
+use crate::data::Action;
+use crate::state::{AppState, Interaction};
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::extract::{Form, FromRequest};
+use axum::http::StatusCode;
+use axum::response::{Response, Result};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Template)]
+#[template(path = "account.html")]
+pub struct AccountPage {
+    account: String,
+    client_id: SecureString,
+    interaction_id: SecureString,
+}
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct AccountParams {
+    #[serde(deserialize_with = "action")]
+    pub action: Action,
+    #[serde(deserialize_with = "interaction_id")]
+    pub interaction_id: SecureString,
+}
+
+// Pauses the authorization request until the user selects the account
+// https://openid.net/specs/openid-connect-core-1_0.html#AuthRequest
+pub fn challenge(
+    state: &AppState,
+    params: AuthorizeParams,
+    sid: Option<SecureString>,
+    login_required: bool,
+) -> Response {
+    let page = AccountPage {
+        account: state.get_user().account(),
+        client_id: params.client_id.clone(),
+        interaction_id: state.gen_secure_string(),
+    };
+    let interaction = Interaction::SelectAccount {
+        login_required,
+        params,
+        sid,
+    };
+    state.set_interaction(page.interaction_id.clone(), interaction);
+    page.into_response()
+}
+
+pub async fn select(state: AppState, params: AccountParams) -> Result<Response> {
+    let Some(Interaction::SelectAccount {
+        login_required,
+        params: request,
+        sid,
+    }) = state.get_interaction(&params.interaction_id)
+    else {
+        let message = format!(
+            "no pending authorization associated with `{}`",
+            params.interaction_id
+        );
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    };
+    if matches!(params.action, Action::Deny) {
+        let description = "the user did not select an account";
+        return Ok(handler::error(
+            &state,
+            request,
+            "access_denied",
+            description,
+        )?);
+    }
+    Ok(handler::authenticate(&state, request, sid, login_required)?)
+}
+
+fn action<'de, D: Deserializer<'de>>(d: D) -> Result<Action, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `action`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn interaction_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `interaction_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID, CODE_CHALLENGE, REDIRECT_URI};
+    use axum::http::StatusCode;
+
+    async fn select_account(state: &crate::state::AppState) -> String {
+        let query = serde_urlencoded::to_string([
+            ("client_id", CLIENT_ID),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("prompt", "select_account"),
+            ("redirect_uri", REDIRECT_URI),
+            ("response_mode", "query"),
+            ("response_type", "code"),
+            ("scope", "openid"),
+            ("state", "12345678901234567890"),
+        ])
+        .unwrap();
+        let response = testing::get(state, &format!("/authorize?{query}")).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.body.contains("john.doe@example.com"));
+        let (_, interaction_id) = response
+            .body
+            .split_once(r#"name="interaction_id" value=""#)
+            .unwrap();
+        interaction_id.split('"').next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn account_selected() {
+        let state = testing::state(&[]);
+        let interaction_id = select_account(&state).await;
+        let params = [("action", "approve"), ("interaction_id", &interaction_id)];
+        let response = testing::post_form(&state, "/login/account", &params).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.redirect_param("code").is_some());
+    }
+
+    #[tokio::test]
+    async fn account_selection_cancelled() {
+        let state = testing::state(&[]);
+        let interaction_id = select_account(&state).await;
+        let params = [("action", "deny"), ("interaction_id", &interaction_id)];
+        let response = testing::post_form(&state, "/login/account", &params).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert_eq!(response.redirect_param("error").unwrap(), "access_denied");
+    }
+}
@@ -0,0 +1,21 @@
+pub mod account;
+pub mod otp;
+pub mod totp;
+
+use crate::state::session::Authentication;
+use crate::state::AppState;
+use axum::response::IntoResponse;
+use axum::Json;
+
+// Test hooks deciding the outcome of the next authentications of the user
+pub async fn authentication(state: AppState) -> impl IntoResponse {
+    Json(state.authentication())
+}
+
+pub async fn set_authentication(
+    state: AppState,
+    Json(authentication): Json<Authentication>,
+) -> impl IntoResponse {
+    state.set_authentication(authentication);
+    Json(state.authentication())
+}
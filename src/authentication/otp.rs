@@ -0,0 +1,229 @@
+use crate::authorize::extractor::AuthorizeParams;
+use crate::authorize::handler;
+use crate::bounded_string::SecureString;
+use crate::data::claims::AcrRequest;
+use crate::data::time::UnixTime;
+use crate::data::AuthenticationMethod;
+use crate::state::{AppState, Interaction};
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::extract::{Form, FromRequest};
+use axum::http::StatusCode;
+use axum::response::{Response, Result};
+use serde::{Deserialize, Deserializer};
+
+#[derive(Template)]
+#[template(path = "otp.html")]
+pub struct OtpPage {
+    error: Option<&'static str>,
+    interaction_id: SecureString,
+}
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct OtpParams {
+    #[serde(deserialize_with = "code")]
+    pub code: String,
+    #[serde(deserialize_with = "interaction_id")]
+    pub interaction_id: SecureString,
+}
+
+// Pauses the authorization request until the user enters the one time password
+pub fn challenge(state: &AppState, params: AuthorizeParams, sid: Option<SecureString>) -> Response {
+    let interaction_id = state.gen_secure_string();
+    let interaction = Interaction::Login {
+        attempts: 0,
+        expires_at: UnixTime::now() + state.otp_expiration(),
+        params,
+        sid,
+    };
+    state.set_interaction(interaction_id.clone(), interaction);
+    let page = OtpPage {
+        error: None,
+        interaction_id,
+    };
+    page.into_response()
+}
+
+pub async fn verify(state: AppState, params: OtpParams) -> Result<Response> {
+    let Some(Interaction::Login {
+        attempts,
+        expires_at,
+        params: request,
+        sid,
+    }) = state.get_interaction(&params.interaction_id)
+    else {
+        let message = format!(
+            "no pending authorization associated with `{}`",
+            params.interaction_id
+        );
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    };
+    if expires_at.expired() {
+        let description = "the one time password was not entered in time";
+        return Ok(handler::error(
+            &state,
+            request,
+            "access_denied",
+            description,
+        )?);
+    }
+    let Some(secret) = state.get_user().totp_secret else {
+        let message = "the user has no one time password secret";
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    };
+    let now = UnixTime::now().into();
+    let verified = secret
+        .verify(&params.code, now)
+        .is_some_and(|step| state.use_totp_step(step));
+    if !verified {
+        let attempts = attempts + 1;
+        if attempts >= state.otp_attempts() {
+            let description = "too many invalid one time passwords";
+            return Ok(handler::error(
+                &state,
+                request,
+                "access_denied",
+                description,
+            )?);
+        }
+        let interaction = Interaction::Login {
+            attempts,
+            expires_at,
+            params: request,
+            sid,
+        };
+        state.set_interaction(params.interaction_id.clone(), interaction);
+        let page = OtpPage {
+            error: Some("Invalid code, try again"),
+            interaction_id: params.interaction_id,
+        };
+        return Ok((StatusCode::UNAUTHORIZED, page).into_response());
+    }
+    // https://datatracker.ietf.org/doc/html/rfc8176#section-2
+    let mut authentication = state.authentication();
+    for method in [AuthenticationMethod::Otp, AuthenticationMethod::Mfa] {
+        if !authentication.amr.contains(&method) {
+            authentication.amr.push(method);
+        }
+    }
+    // The second factor raises the level of the authentication
+    let otp_acr = AcrRequest {
+        essential: true,
+        values: vec![state.otp_acr()],
+    };
+    if !otp_acr.accepts(&authentication.acr) {
+        authentication.acr = state.otp_acr();
+    }
+    Ok(handler::resume(&state, request, sid, Some(authentication))?)
+}
+
+fn code<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `code`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn interaction_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `interaction_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::authentication::totp::TotpSecret;
+    use crate::data::time::UnixTime;
+    use crate::state::AppState;
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET, CODE_VERIFIER, REDIRECT_URI};
+    use axum::http::StatusCode;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::Value;
+
+    const TOTP_SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+    const INVALID_CODE: &str = "000000";
+
+    fn state(overrides: &[(&str, &str)]) -> AppState {
+        testing::state_with(&[("USER__TOTP_SECRET", TOTP_SECRET)], overrides)
+    }
+
+    fn current_code() -> String {
+        let secret = TotpSecret::try_from(TOTP_SECRET.to_string()).unwrap();
+        let code = secret.code_at(UnixTime::now().into());
+        assert_ne!(code, INVALID_CODE);
+        code
+    }
+
+    async fn challenge(state: &AppState) -> String {
+        let response = testing::authorize(state, CLIENT_ID).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let (_, interaction_id) = response
+            .body
+            .split_once(r#"name="interaction_id" value=""#)
+            .unwrap();
+        interaction_id.split('"').next().unwrap().to_string()
+    }
+
+    async fn verify(state: &AppState, interaction_id: &str, code: &str) -> testing::TestResponse {
+        let params = [("code", code), ("interaction_id", interaction_id)];
+        testing::post_form(state, "/login/otp", &params).await
+    }
+
+    #[tokio::test]
+    async fn otp_raises_acr() {
+        let state = state(&[]);
+        let interaction_id = challenge(&state).await;
+        let response = verify(&state, &interaction_id, &current_code()).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("code", &response.redirect_param("code").unwrap()),
+            ("code_verifier", CODE_VERIFIER),
+            ("redirect_uri", REDIRECT_URI),
+        ];
+        let response = testing::post_form(&state, "/token", &params).await;
+        let id_token = response.json()["id_token"].as_str().unwrap().to_string();
+        let claims = id_token.split('.').nth(1).unwrap();
+        let claims: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap();
+        assert_eq!(claims["acr"], "2");
+        assert_eq!(claims["amr"], serde_json::json!(["pwd", "otp", "mfa"]));
+    }
+
+    #[tokio::test]
+    async fn otp_attempts_limited() {
+        let state = state(&[("OTP_ATTEMPTS", "2")]);
+        let interaction_id = challenge(&state).await;
+        let response = verify(&state, &interaction_id, INVALID_CODE).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        let response = verify(&state, &interaction_id, INVALID_CODE).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert_eq!(response.redirect_param("error").unwrap(), "access_denied");
+        let response = verify(&state, &interaction_id, &current_code()).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn otp_expired() {
+        let state = state(&[("OTP_EXPIRATION", "0")]);
+        let interaction_id = challenge(&state).await;
+        let response = verify(&state, &interaction_id, &current_code()).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert_eq!(response.redirect_param("error").unwrap(), "access_denied");
+    }
+
+    #[tokio::test]
+    async fn otp_not_replayed() {
+        let state = state(&[]);
+        let code = current_code();
+        let interaction_id = challenge(&state).await;
+        let response = verify(&state, &interaction_id, &code).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        let interaction_id = challenge(&state).await;
+        let response = verify(&state, &interaction_id, &code).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
+}
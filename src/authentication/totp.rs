@@ -0,0 +1,121 @@
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha1::Sha1;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc6238#section-4
+const TIME_STEP: u64 = 30;
+const DIGITS: u32 = 6;
+// Accept the codes of the adjacent time steps to tolerate clock drifts
+const WINDOW: u64 = 1;
+const BASE32_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TotpSecret(Vec<u8>);
+
+#[derive(Debug, Error)]
+#[error("invalid base32 totp secret `{0}`")]
+pub struct TotpSecretError(String);
+
+impl TryFrom<String> for TotpSecret {
+    type Error = TotpSecretError;
+
+    // https://datatracker.ietf.org/doc/html/rfc4648#section-6
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut bytes = Vec::new();
+        let mut buffer = 0u64;
+        let mut bits = 0;
+        for c in value.trim_end_matches('=').chars().filter(|c| *c != ' ') {
+            let c = c.to_ascii_uppercase() as u8;
+            let index = BASE32_ALPHABET
+                .iter()
+                .position(|a| *a == c)
+                .ok_or_else(|| TotpSecretError(value.clone()))?;
+            buffer = (buffer << 5) | index as u64;
+            bits += 5;
+            if bits >= 8 {
+                bits -= 8;
+                bytes.push((buffer >> bits) as u8);
+            }
+        }
+        if bytes.is_empty() {
+            return Err(TotpSecretError(value));
+        }
+        Ok(Self(bytes))
+    }
+}
+
+impl TotpSecret {
+    // https://datatracker.ietf.org/doc/html/rfc4226#section-5.3
+    fn code(&self, counter: u64) -> u32 {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.0).unwrap();
+        mac.update(&counter.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().unwrap()) & 0x7fffffff;
+        binary % 10u32.pow(DIGITS)
+    }
+
+    #[cfg(test)]
+    pub fn code_at(&self, time: u64) -> String {
+        format!("{:06}", self.code(time / TIME_STEP))
+    }
+
+    // The time step of the code, which is exactly `DIGITS` ASCII digits
+    pub fn verify(&self, code: &str, time: u64) -> Option<u64> {
+        if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let counter = time / TIME_STEP;
+        (counter.saturating_sub(WINDOW)..=counter + WINDOW)
+            .find(|c| format!("{:06}", self.code(*c)) == code)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The base32 encoding of the RFC 6238 test secret `12345678901234567890`
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn rfc_test_vector() {
+        let secret = TotpSecret::try_from(SECRET.to_string()).unwrap();
+        assert_eq!(secret.verify("287082", 59), Some(1))
+    }
+
+    #[test]
+    fn adjacent_time_step() {
+        let secret = TotpSecret::try_from(SECRET.to_string()).unwrap();
+        assert_eq!(secret.verify("287082", 59 + TIME_STEP), Some(1));
+        assert_eq!(secret.verify("287082", 59 + 2 * TIME_STEP), None)
+    }
+
+    #[test]
+    fn malformed_code() {
+        let secret = TotpSecret::try_from(SECRET.to_string()).unwrap();
+        for code in ["+287082", "0287082", " 287082", "287082 ", "28708２"] {
+            assert_eq!(secret.verify(code, 59), None, "{code}")
+        }
+    }
+
+    #[test]
+    fn leading_zero_required() {
+        let secret = TotpSecret::try_from(SECRET.to_string()).unwrap();
+        let step = (0..).find(|step| secret.code(*step) < 100000).unwrap();
+        let code = secret.code(step);
+        assert_eq!(
+            secret.verify(&format!("{code:06}"), step * TIME_STEP),
+            Some(step)
+        );
+        assert_eq!(secret.verify(&code.to_string(), step * TIME_STEP), None)
+    }
+
+    #[test]
+    fn invalid_secret() {
+        let result = TotpSecret::try_from("not base32!".to_string());
+        assert!(result.is_err())
+    }
+}
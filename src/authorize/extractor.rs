@@ -1,28 +1,54 @@
-use crate::bounded_string::SecureString;
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::data::authorization_details::AuthorizationDetail;
+use crate::data::claims::{AcrRequest, ClaimsRequest};
 use crate::data::pkce::CodeChallenge;
-use crate::data::{CodeChallengeMethod, ResponseMode, ResponseType, Scope};
+use crate::data::resource::join_resources;
+use crate::data::{CodeChallengeMethod, Delivery, Prompt, ResponseMode, ResponseType, Scope};
+use axum::async_trait;
+use axum::extract::rejection::QueryRejection;
 use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
 use axum::http::Uri;
 use serde::{Deserialize, Deserializer};
 use serde_with::formats::SpaceSeparator;
-use serde_with::StringWithSeparator;
+use serde_with::json::JsonString;
 use serde_with::{serde_as, DeserializeAs};
-use std::collections::HashSet;
+use serde_with::{DisplayFromStr, PickFirst, Same, StringWithSeparator};
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
+pub enum AuthorizeRequest {
+    Params(Box<AuthorizeParams>),
+    RequestObject(RequestObjectParams),
+    RequestUri(RequestUriParams),
+}
+
 #[serde_as]
-#[derive(Clone, Deserialize, FromRequestParts)]
-#[from_request(via(Query))]
+#[derive(Clone, Deserialize)]
 #[allow(unused)]
 pub struct AuthorizeParams {
+    #[serde(default, deserialize_with = "acr_values")]
+    pub acr_values: Vec<NonEmptyString>,
+    #[serde(default, deserialize_with = "authorization_details")]
+    pub authorization_details: Vec<AuthorizationDetail>,
+    #[serde(default, deserialize_with = "claims")]
+    pub claims: ClaimsRequest,
     #[serde(deserialize_with = "client_id")]
     pub client_id: SecureString,
-    #[serde(deserialize_with = "code_challenge_method")]
-    pub code_challenge_method: CodeChallengeMethod,
-    #[serde(deserialize_with = "code_challenge")]
-    pub code_challenge: CodeChallenge,
+    #[serde(default, deserialize_with = "code_challenge_method")]
+    pub code_challenge_method: Option<CodeChallengeMethod>,
+    #[serde(default, deserialize_with = "code_challenge")]
+    pub code_challenge: Option<CodeChallenge>,
+    #[serde(default, deserialize_with = "max_age")]
+    pub max_age: Option<u64>,
+    #[serde(default, deserialize_with = "nonce")]
+    pub nonce: Option<NonEmptyString>,
+    #[serde(default, deserialize_with = "prompt")]
+    pub prompt: HashSet<Prompt>,
     #[serde(deserialize_with = "redirect_uri")]
     pub redirect_uri: Uri,
+    #[serde(default, deserialize_with = "resource")]
+    pub resource: Vec<Uri>,
     #[serde(deserialize_with = "response_mode")]
     pub response_mode: ResponseMode,
     #[serde(deserialize_with = "response_type")]
@@ -33,30 +59,159 @@ pub struct AuthorizeParams {
     pub state: SecureString,
 }
 
+// https://datatracker.ietf.org/doc/html/rfc9126#section-2.2
+pub const PUSHED_REQUEST_URI_PREFIX: &str = "urn:ietf:params:oauth:request_uri:";
+
+// https://datatracker.ietf.org/doc/html/rfc9126#section-4
+#[derive(Deserialize)]
+pub struct RequestUriParams {
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(deserialize_with = "request_uri")]
+    pub request_uri: NonEmptyString,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9101#section-5
+pub struct RequestObjectParams {
+    pub client_id: SecureString,
+    pub query: HashMap<String, String>,
+    pub request: RequestObject,
+}
+
+pub enum RequestObject {
+    Value(String),
+    Reference(String),
+}
+
+#[derive(Deserialize)]
+struct ClientIdParam {
+    #[serde(deserialize_with = "client_id")]
+    client_id: SecureString,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthorizeRequest {
+    type Rejection = QueryRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let joined = join_resources(parts.uri.query().unwrap_or_default().as_bytes());
+        if let Some(uri) =
+            joined.and_then(|query| format!("{}?{query}", parts.uri.path()).parse::<Uri>().ok())
+        {
+            parts.uri = uri;
+        }
+        let mut query: HashMap<String, String> =
+            serde_urlencoded::from_str(parts.uri.query().unwrap_or_default()).unwrap_or_default();
+        let request = match (query.remove("request"), query.remove("request_uri")) {
+            (Some(value), _) => RequestObject::Value(value),
+            (None, Some(uri)) if !uri.starts_with(PUSHED_REQUEST_URI_PREFIX) => {
+                RequestObject::Reference(uri)
+            }
+            (None, Some(_)) => {
+                let Query(params) = Query::from_request_parts(parts, state).await?;
+                return Ok(Self::RequestUri(params));
+            }
+            (None, None) => {
+                let Query(params) = Query::from_request_parts(parts, state).await?;
+                return Ok(Self::Params(Box::new(params)));
+            }
+        };
+        let Query(ClientIdParam { client_id }) = Query::from_request_parts(parts, state).await?;
+        Ok(Self::RequestObject(RequestObjectParams {
+            client_id,
+            query,
+            request,
+        }))
+    }
+}
+
+fn acr_values<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<NonEmptyString>, D::Error> {
+    StringWithSeparator::<SpaceSeparator, NonEmptyString>::deserialize_as(d)
+        .map_err(|e| format!("error while parsing field `acr_values`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+// Query and form parameters are JSON strings while request object claims are objects
+// https://datatracker.ietf.org/doc/html/rfc9396#section-3
+fn authorization_details<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Vec<AuthorizationDetail>, D::Error> {
+    PickFirst::<(Same, JsonString)>::deserialize_as(d)
+        .map_err(|e| format!("error while parsing field `authorization_details`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn claims<'de, D: Deserializer<'de>>(d: D) -> Result<ClaimsRequest, D::Error> {
+    PickFirst::<(Same, JsonString)>::deserialize_as(d)
+        .map_err(|e| format!("error while parsing field `claims`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
 fn client_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
     Deserialize::deserialize(d)
         .map_err(|e| format!("error while parsing field `client_id`: {e}"))
         .map_err(serde::de::Error::custom)
 }
 
-fn code_challenge_method<'de, D: Deserializer<'de>>(d: D) -> Result<CodeChallengeMethod, D::Error> {
+fn code_challenge_method<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<CodeChallengeMethod>, D::Error> {
     Deserialize::deserialize(d)
+        .map(Some)
         .map_err(|e| format!("error while parsing field `code_challenge_method`: {e}"))
         .map_err(serde::de::Error::custom)
 }
 
-fn code_challenge<'de, D: Deserializer<'de>>(d: D) -> Result<CodeChallenge, D::Error> {
+fn code_challenge<'de, D: Deserializer<'de>>(d: D) -> Result<Option<CodeChallenge>, D::Error> {
     Deserialize::deserialize(d)
+        .map(Some)
         .map_err(|e| format!("error while parsing field `code_challenge`: {e}"))
         .map_err(serde::de::Error::custom)
 }
 
+// Query and form parameters are strings while request object claims are numbers
+fn max_age<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    PickFirst::<(Same, DisplayFromStr)>::deserialize_as(d)
+        .map(Some)
+        .map_err(|e| format!("error while parsing field `max_age`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn nonce<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NonEmptyString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map(Some)
+        .map_err(|e| format!("error while parsing field `nonce`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn prompt<'de, D: Deserializer<'de>>(d: D) -> Result<HashSet<Prompt>, D::Error> {
+    StringWithSeparator::<SpaceSeparator, Prompt>::deserialize_as(d)
+        .map_err(|e| format!("error while parsing field `prompt`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
 fn redirect_uri<'de, D: Deserializer<'de>>(d: D) -> Result<Uri, D::Error> {
     http_serde::uri::deserialize(d)
         .map_err(|e| format!("error while parsing field `redirect_uri`: {e}"))
         .map_err(serde::de::Error::custom)
 }
 
+fn request_uri<'de, D: Deserializer<'de>>(d: D) -> Result<NonEmptyString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `request_uri`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+// Repeated in the query and the forms, an array in the request objects
+pub fn resource<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<Uri>, D::Error> {
+    PickFirst::<(
+        StringWithSeparator<SpaceSeparator, Uri>,
+        Vec<DisplayFromStr>,
+    )>::deserialize_as(d)
+    .map_err(|e| format!("error while parsing field `resource`: {e}"))
+    .map_err(serde::de::Error::custom)
+}
+
 fn response_mode<'de, D: Deserializer<'de>>(d: D) -> Result<ResponseMode, D::Error> {
     Deserialize::deserialize(d)
         .map_err(|e| format!("error while parsing field `response_mode`: {e}"))
@@ -69,7 +224,7 @@ fn response_type<'de, D: Deserializer<'de>>(d: D) -> Result<ResponseType, D::Err
         .map_err(serde::de::Error::custom)
 }
 
-fn scope<'de, D: Deserializer<'de>>(d: D) -> Result<HashSet<Scope>, D::Error> {
+pub fn scope<'de, D: Deserializer<'de>>(d: D) -> Result<HashSet<Scope>, D::Error> {
     StringWithSeparator::<SpaceSeparator, Scope>::deserialize_as(d)
         .and_then(|s| validate_scope(s).map_err(serde::de::Error::custom))
         .map_err(|e| format!("error while parsing field `scope`: {e}"))
@@ -83,9 +238,52 @@ fn state<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
 }
 
 #[derive(Debug, Error)]
-enum Error {
+pub enum Error {
     #[error("missing scope `{0}`")]
     MissingScope(Scope),
+    #[error("missing parameter `nonce`, required when `response_type` includes `id_token`")]
+    MissingNonce,
+    #[error("missing parameters `code_challenge` and `code_challenge_method`, required when `response_type` includes `code`")]
+    MissingCodeChallenge,
+    #[error("`response_mode` must not be `query` when `response_type` includes tokens")]
+    QueryResponseMode,
+    #[error("`prompt` must not combine `none` with other values")]
+    PromptNone,
+}
+
+impl AuthorizeParams {
+    // The `acr` claim request takes precedence over the voluntary `acr_values`
+    pub fn acr_request(&self) -> AcrRequest {
+        match self.claims.id_token.acr.clone() {
+            Some(claim) => claim.into(),
+            None => AcrRequest {
+                essential: false,
+                values: self.acr_values.clone(),
+            },
+        }
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#ImplicitAuthRequest
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.response_type.id_token() && self.nonce.is_none() {
+            return Err(Error::MissingNonce);
+        }
+        // https://datatracker.ietf.org/doc/html/rfc7636#section-4.3
+        let pkce = self.code_challenge.is_some() && self.code_challenge_method.is_some();
+        if self.response_type.code() && !pkce {
+            return Err(Error::MissingCodeChallenge);
+        }
+        // https://openid.net/specs/oauth-v2-multiple-response-types-1_0.html#Security
+        let has_tokens = self.response_type.id_token() || self.response_type.token();
+        let delivery = self.response_mode.delivery(&self.response_type);
+        if has_tokens && delivery == Delivery::Query {
+            return Err(Error::QueryResponseMode);
+        }
+        if self.prompt.contains(&Prompt::None) && self.prompt.len() > 1 {
+            return Err(Error::PromptNone);
+        }
+        Ok(())
+    }
 }
 
 fn validate_scope(set: HashSet<Scope>) -> Result<HashSet<Scope>, Error> {
@@ -95,3 +293,30 @@ fn validate_scope(set: HashSet<Scope>) -> Result<HashSet<Scope>, Error> {
         Err(Error::MissingScope(Scope::Openid))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const QUERY: &str = "client_id=0jrabyQWm4B9zVJPbotY&redirect_uri=http://localhost:3000/success&scope=openid&state=12345678901234567890&nonce=n-0S6_WzA2Mj&response_mode=fragment";
+    const PKCE: &str =
+        "code_challenge_method=S256&code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    fn params(query: &str) -> AuthorizeParams {
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    #[test]
+    fn implicit_without_pkce() {
+        let params = params(&format!("{QUERY}&response_type=id_token%20token"));
+        assert!(params.validate().is_ok());
+    }
+
+    #[test]
+    fn code_requires_pkce() {
+        let code = params(&format!("{QUERY}&response_type=code%20id_token"));
+        assert!(matches!(code.validate(), Err(Error::MissingCodeChallenge)));
+        let code = params(&format!("{QUERY}&response_type=code%20id_token&{PKCE}"));
+        assert!(code.validate().is_ok());
+    }
+}
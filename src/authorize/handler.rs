@@ -1,37 +1,515 @@
-use super::extractor::AuthorizeParams;
-use crate::bounded_string::SecureString;
-use crate::state::{AppState, AuthSession};
+use super::extractor::{self, AuthorizeParams, AuthorizeRequest, RequestUriParams};
+use super::request_object;
+use crate::authentication::{account, otp};
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::consent;
+use crate::data::time::UnixTime;
+use crate::data::{Delivery, Prompt};
+use crate::state::client::GrantType;
+use crate::state::session::Authentication;
+use crate::state::session::{session_cookie, SESSION_COOKIE};
+use crate::state::{AppState, AuthSession, Grant};
+use crate::token::{issue, jwt};
 use askama::Template;
 use askama_axum::IntoResponse;
-use axum::http::{HeaderMap, Uri};
-use headers::{CacheControl, ContentType, HeaderMapExt, Pragma};
+use axum::http::header::InvalidHeaderValue;
+use axum::http::header::{LOCATION, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::response::{Response, Result};
+use axum_extra::TypedHeader;
+use headers::{CacheControl, ContentType, Cookie, HeaderMapExt, Pragma};
+use serde::Serialize;
+use std::collections::HashMap;
+use thiserror::Error;
 
 #[derive(Template)]
 #[template(path = "form.html")]
 pub struct AuthorizeResponse {
-    code: SecureString,
+    params: Vec<(&'static str, String)>,
     redirect_uri: Uri,
-    state: SecureString,
 }
 
-pub async fn authorize(state: AppState, params: AuthorizeParams) -> impl IntoResponse {
-    let session = AuthSession {
-        client_id: params.client_id,
-        code_challenge: params.code_challenge,
-        redirect_uri: params.redirect_uri.clone(),
-        scope: params.scope,
-        user_id: state.gen_secure_string(),
+#[derive(Serialize)]
+struct ResponseToken {
+    aud: SecureString,
+    exp: UnixTime,
+    #[serde(with = "http_serde::uri")]
+    iss: Uri,
+    #[serde(flatten)]
+    params: HashMap<&'static str, String>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidParam(#[from] extractor::Error),
+    #[error(transparent)]
+    Issue(#[from] issue::Error),
+    #[error("response parameters encode error: `{0}`")]
+    Encode(#[from] serde_urlencoded::ser::Error),
+    #[error("invalid redirection location: `{0}`")]
+    Location(#[from] InvalidHeaderValue),
+    #[error("`redirect_uri` `{0}` is not registered for client `{1}`")]
+    RedirectUri(Uri, SecureString),
+    #[error("client `{0}` has been deleted")]
+    DeletedClient(SecureString),
+    #[error("client `{0}` must push its authorization requests")]
+    PushedAuthorizationRequired(SecureString),
+    #[error("`request_uri` `{0}` is invalid, expired or bound to another client")]
+    RequestUri(NonEmptyString),
+    #[error(transparent)]
+    RequestObject(#[from] request_object::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let response = match self {
+            Self::Issue(_) | Self::Encode(_) | Self::Location(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
+            }
+            Self::RequestObject(e) => return e.into_response(),
+            _ => (StatusCode::BAD_REQUEST, self.to_string()),
+        };
+        response.into_response()
+    }
+}
+
+pub async fn authorize(
+    state: AppState,
+    cookie: Option<TypedHeader<Cookie>>,
+    request: AuthorizeRequest,
+) -> Result<Response> {
+    let params = match request {
+        AuthorizeRequest::Params(params) => direct_request(&state, *params)?,
+        AuthorizeRequest::RequestObject(params) => {
+            let params = request_object::resolve(&state, params)
+                .await
+                .map_err(Error::from)?;
+            direct_request(&state, params)?
+        }
+        AuthorizeRequest::RequestUri(params) => pushed_request(&state, params)?,
     };
-    let code: SecureString = state.gen_secure_string();
-    state.set_session(code.clone(), session);
+    params.validate().map_err(Error::from)?;
+    if state.is_deleted_client(&params.client_id) {
+        return Err(Error::DeletedClient(params.client_id).into());
+    }
+    // An unregistered redirection URI is not trusted with the error response
+    let client = state.client(&params.client_id);
+    if !client.allows_redirect_uri(&params.redirect_uri) {
+        return Err(Error::RedirectUri(params.redirect_uri, params.client_id).into());
+    }
+    let grant_types = [
+        (params.response_type.code(), GrantType::AuthorizationCode),
+        (
+            params.response_type.id_token() || params.response_type.token(),
+            GrantType::Implicit,
+        ),
+    ];
+    if let Some((_, grant_type)) = grant_types
+        .into_iter()
+        .find(|(requested, grant_type)| *requested && !client.allows_grant_type(*grant_type))
+    {
+        let description = format!("client is not registered for the `{grant_type}` grant type");
+        return Ok(error(&state, params, "unauthorized_client", &description)?);
+    }
+    // https://datatracker.ietf.org/doc/html/rfc8707#section-2
+    if let Some(resource) = params.resource.iter().find(|uri| !state.is_resource(uri)) {
+        let description = format!("unknown resource `{resource}`");
+        return Ok(error(&state, params, "invalid_target", &description)?);
+    }
+    if let Err(e) = state.validate_authorization_details(&params.authorization_details) {
+        return Ok(error(&state, params, e.code(), &e.to_string())?);
+    }
+    // https://openid.net/specs/openid-connect-core-1_0.html#Authenticates
+    let session = cookie
+        .as_ref()
+        .and_then(|TypedHeader(cookie)| cookie.get(SESSION_COOKIE))
+        .and_then(|sid| SecureString::try_from(sid).ok())
+        .and_then(|sid| Some((state.user_session(&sid)?, sid)));
+    let acr_request = params.acr_request();
+    // A session authenticated with an unrequested `acr` is stepped up, unless the
+    // user cannot be prompted and the `acr` is only a preference
+    let login_required = match &session {
+        Some((session, _)) => {
+            params.prompt.contains(&Prompt::Login)
+                || params
+                    .max_age
+                    .is_some_and(|max_age| session.is_older_than(max_age))
+                || (!acr_request.accepts(&session.authentication.acr)
+                    && (acr_request.essential || !params.prompt.contains(&Prompt::None)))
+        }
+        None => true,
+    };
+    if login_required && params.prompt.contains(&Prompt::None) {
+        let description = match session {
+            Some(_) => "the user must authenticate again but `prompt` is `none`",
+            None => "no active session and `prompt` is `none`",
+        };
+        return Ok(error(&state, params, "login_required", description)?);
+    }
+    let sid = session.map(|(_, sid)| sid);
+    // The user confirms the account even though there is a single one
+    if params.prompt.contains(&Prompt::SelectAccount) {
+        return Ok(account::challenge(&state, params, sid, login_required));
+    }
+    Ok(authenticate(&state, params, sid, login_required)?)
+}
+
+// Authenticates the user when the session cannot be reused
+pub fn authenticate(
+    state: &AppState,
+    params: AuthorizeParams,
+    sid: Option<SecureString>,
+    login_required: bool,
+) -> Result<Response, Error> {
+    if !login_required {
+        return resume(state, params, sid, None);
+    }
+    if state.get_user().totp_secret.is_some() {
+        return Ok(otp::challenge(state, params, sid));
+    }
+    resume(state, params, sid, Some(state.authentication()))
+}
+
+// Completes the authorization request, recording the authentication of the user if it took place
+pub fn resume(
+    state: &AppState,
+    params: AuthorizeParams,
+    sid: Option<SecureString>,
+    authentication: Option<Authentication>,
+) -> Result<Response, Error> {
+    // The session may have ended since it was read, a new one is then started
+    let session = sid.and_then(|sid| {
+        let session = state.join_session(&sid, &params.client_id, authentication.clone())?;
+        Some((sid, session))
+    });
+    let (sid, session) = match session {
+        Some(session) => session,
+        None => {
+            let authentication = authentication.unwrap_or_else(|| state.authentication());
+            state.create_session(&params.client_id, &state.get_user().id(), authentication)
+        }
+    };
+    let cookie = session_cookie(&sid, state.session_expiration());
+    let acr_request = params.acr_request();
+    // https://openid.net/specs/openid-connect-core-1_0.html#AuthError
+    if acr_request.essential && !acr_request.accepts(&session.authentication.acr) {
+        let description = format!(
+            "the authentication achieved `acr` `{}` which is not requested",
+            session.authentication.acr
+        );
+        let mut response = error(
+            state,
+            params,
+            "unmet_authentication_requirements",
+            &description,
+        )?;
+        response.headers_mut().insert(SET_COOKIE, cookie);
+        return Ok(response);
+    }
+    let mut grant = Grant {
+        auth_time: session.auth_time,
+        authentication: session.authentication,
+        authorization_details: params.authorization_details.clone(),
+        client_id: params.client_id.clone(),
+        nonce: params.nonce.clone(),
+        resource: params.resource.clone(),
+        scope: params.scope.clone(),
+        sid: Some(sid),
+        user_id: session.user_id,
+    };
+    // https://openid.net/specs/openid-connect-core-1_0.html#Consent
+    if state.consent_required() {
+        let consented = state
+            .consented_scopes(&grant.user_id, &grant.client_id, &grant.scope)
+            .filter(|_| !params.prompt.contains(&Prompt::Consent));
+        let mut response = match consented {
+            Some(scope) => {
+                grant.scope = scope;
+                complete(state, params, grant)?
+            }
+            None if params.prompt.contains(&Prompt::None) => {
+                let description = "the user must consent but `prompt` is `none`";
+                error(state, params, "consent_required", description)?
+            }
+            None => consent::challenge(state, grant, params),
+        };
+        response.headers_mut().insert(SET_COOKIE, cookie);
+        return Ok(response);
+    }
+    let mut response = complete(state, params, grant)?;
+    response.headers_mut().insert(SET_COOKIE, cookie);
+    Ok(response)
+}
+
+// Delivers the response of the authorization request to the client
+pub fn complete(
+    state: &AppState,
+    params: AuthorizeParams,
+    grant: Grant,
+) -> Result<Response, Error> {
+    let mut response = Vec::new();
+    let code = params
+        .response_type
+        .code()
+        .then(|| state.gen_secure_string());
+    if let Some(code) = &code {
+        response.push(("code", code.to_string()));
+    }
+    let audience = state.token_audience(&grant.resource);
+    let access_token = if params.response_type.token() {
+        Some(issue::access_token(state, &grant, &audience, None)?)
+    } else {
+        None
+    };
+    if let Some(access_token) = &access_token {
+        response.push(("access_token", access_token.clone()));
+        response.push(("token_type", "Bearer".into()));
+        response.push(("expires_in", audience.expiration.to_string()));
+    }
+    if params.response_type.id_token() {
+        let id_token = issue::id_token(state, &grant, access_token.as_deref(), code.as_ref())?;
+        response.push(("id_token", id_token));
+    }
+    response.push(("state", params.state.to_string()));
+    if params.response_mode.is_jwt() {
+        response = secure_response(state, &grant.client_id, response)?;
+    }
+    // The request was validated to carry a code challenge along with the code response type
+    if let Some((code, code_challenge)) = code.zip(params.code_challenge) {
+        let session = AuthSession {
+            code_challenge,
+            grant,
+            redirect_uri: params.redirect_uri.clone(),
+        };
+        state.set_session(code, session);
+    }
+    let delivery = params.response_mode.delivery(&params.response_type);
+    respond(delivery, params.redirect_uri, response)
+}
+
+// https://openid.net/specs/openid-connect-core-1_0.html#AuthError
+pub fn error(
+    state: &AppState,
+    params: AuthorizeParams,
+    error: &'static str,
+    description: &str,
+) -> Result<Response, Error> {
+    let mut response = vec![
+        ("error", error.to_string()),
+        ("error_description", description.to_string()),
+        ("state", params.state.to_string()),
+    ];
+    if params.response_mode.is_jwt() {
+        response = secure_response(state, &params.client_id, response)?;
+    }
+    let delivery = params.response_mode.delivery(&params.response_type);
+    respond(delivery, params.redirect_uri, response)
+}
+
+fn direct_request(state: &AppState, params: AuthorizeParams) -> Result<AuthorizeParams, Error> {
+    if state
+        .client(&params.client_id)
+        .require_pushed_authorization_requests
+    {
+        Err(Error::PushedAuthorizationRequired(params.client_id))
+    } else {
+        Ok(params)
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9126#section-4
+fn pushed_request(state: &AppState, params: RequestUriParams) -> Result<AuthorizeParams, Error> {
+    state
+        .get_pushed_request(&params.request_uri)
+        .map(|request| request.params)
+        .filter(|request| request.client_id == params.client_id)
+        .ok_or(Error::RequestUri(params.request_uri))
+}
+
+// https://openid.net/specs/oauth-v2-jarm.html#section-2.1
+fn secure_response(
+    state: &AppState,
+    client_id: &SecureString,
+    params: Vec<(&'static str, String)>,
+) -> Result<Vec<(&'static str, String)>, issue::Error> {
+    let claims = ResponseToken {
+        aud: client_id.clone(),
+        exp: UnixTime::now() + state.jarm_expiration(),
+        iss: state.issuer(),
+        params: params.into_iter().collect(),
+    };
+    let response = jwt::encode(claims, state.signing_key())?;
+    Ok(vec![("response", response)])
+}
+
+fn respond(
+    delivery: Delivery,
+    redirect_uri: Uri,
+    params: Vec<(&'static str, String)>,
+) -> Result<Response, Error> {
     let mut headers = HeaderMap::new();
-    headers.typed_insert(ContentType::html());
     headers.typed_insert(CacheControl::new().with_no_cache().with_no_store());
     headers.typed_insert(Pragma::no_cache());
-    let response = AuthorizeResponse {
-        code,
-        redirect_uri: params.redirect_uri,
-        state: params.state,
+    let encoded = serde_urlencoded::to_string(&params)?;
+    let location = match delivery {
+        Delivery::FormPost => {
+            headers.typed_insert(ContentType::html());
+            let response = AuthorizeResponse {
+                params,
+                redirect_uri,
+            };
+            return Ok((headers, response).into_response());
+        }
+        Delivery::Fragment => format!("{redirect_uri}#{encoded}"),
+        Delivery::Query if redirect_uri.query().is_some() => format!("{redirect_uri}&{encoded}"),
+        Delivery::Query => format!("{redirect_uri}?{encoded}"),
     };
-    (headers, response)
+    headers.insert(LOCATION, HeaderValue::from_str(&location)?);
+    Ok((StatusCode::FOUND, headers).into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::time::UnixTime;
+    use crate::state::AppState;
+    use crate::testing::{self, CLIENT_ID, CODE_CHALLENGE, REDIRECT_URI};
+    use axum::body::Body;
+    use axum::http::header::{COOKIE, SET_COOKIE};
+    use axum::http::{Method, StatusCode};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::Value;
+
+    async fn authorize(
+        state: &AppState,
+        params: &[(&str, &str)],
+        cookie: Option<&str>,
+    ) -> testing::TestResponse {
+        let defaults = [
+            ("client_id", CLIENT_ID),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", REDIRECT_URI),
+            ("response_mode", "query"),
+            ("response_type", "code"),
+            ("scope", "openid"),
+            ("state", "12345678901234567890"),
+        ];
+        let query = serde_urlencoded::to_string(testing::merge(&defaults, params)).unwrap();
+        let mut request = testing::request(Method::GET, &format!("/authorize?{query}"));
+        if let Some(cookie) = cookie {
+            request = request.header(COOKIE, cookie);
+        }
+        testing::send(state, request.body(Body::empty()).unwrap()).await
+    }
+
+    // The `name=value` pair of the session cookie
+    fn session_cookie(response: &testing::TestResponse) -> String {
+        let cookie = response.header(SET_COOKIE.as_str());
+        cookie.split(';').next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn jwt_response_expires_with_jarm_expiration() {
+        let state = testing::state(&[("EXPIRATION", "3600"), ("JARM_EXPIRATION", "30")]);
+        let response = authorize(&state, &[("response_mode", "jwt")], None).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.header("location").contains('?'));
+        let jwt = response.redirect_param("response").unwrap();
+        let claims = jwt.split('.').nth(1).unwrap();
+        let claims: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap();
+        let exp = claims["exp"].as_u64().unwrap();
+        let now: u64 = UnixTime::now().into();
+        assert!(exp > now && exp <= now + 30);
+    }
+
+    #[tokio::test]
+    async fn session_cookie_attributes() {
+        let state = testing::state(&[("SESSION_EXPIRATION", "3600")]);
+        let response = authorize(&state, &[], None).await;
+        let cookie = response.header(SET_COOKIE.as_str());
+        assert!(cookie.contains("; Secure") && cookie.contains("; Max-Age=3600"));
+    }
+
+    #[tokio::test]
+    async fn prompt_none_without_session() {
+        let state = testing::state(&[]);
+        let response = authorize(&state, &[("prompt", "none")], None).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert_eq!(response.redirect_param("error").unwrap(), "login_required");
+    }
+
+    #[tokio::test]
+    async fn prompt_none_with_session() {
+        let state = testing::state(&[]);
+        let cookie = session_cookie(&authorize(&state, &[], None).await);
+        let response = authorize(&state, &[("prompt", "none")], Some(&cookie)).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.redirect_param("code").is_some());
+    }
+
+    #[tokio::test]
+    async fn prompt_none_with_stale_session() {
+        let state = testing::state(&[]);
+        let cookie = session_cookie(&authorize(&state, &[], None).await);
+        let params = [("prompt", "none"), ("max_age", "0")];
+        let response = authorize(&state, &params, Some(&cookie)).await;
+        assert_eq!(response.redirect_param("error").unwrap(), "login_required");
+    }
+
+    #[tokio::test]
+    async fn prompt_none_with_voluntary_acr() {
+        let state = testing::state(&[]);
+        let cookie = session_cookie(&authorize(&state, &[], None).await);
+        let params = [("prompt", "none"), ("acr_values", "2")];
+        let response = authorize(&state, &params, Some(&cookie)).await;
+        assert!(
+            response.redirect_param("code").is_some(),
+            "{}",
+            response.body
+        );
+    }
+
+    #[tokio::test]
+    async fn prompt_none_with_essential_acr() {
+        let state = testing::state(&[]);
+        let cookie = session_cookie(&authorize(&state, &[], None).await);
+        let claims = r#"{"id_token":{"acr":{"essential":true,"values":["2"]}}}"#;
+        let params = [("prompt", "none"), ("claims", claims)];
+        let response = authorize(&state, &params, Some(&cookie)).await;
+        assert_eq!(response.redirect_param("error").unwrap(), "login_required");
+    }
+
+    #[tokio::test]
+    async fn consent_outlives_the_session() {
+        let state = testing::state(&[("CONSENT_REQUIRED", "true")]);
+        let response = authorize(&state, &[], None).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let (_, rest) = response
+            .body
+            .split_once(r#"name="interaction_id" value=""#)
+            .unwrap();
+        let (interaction_id, _) = rest.split_once('"').unwrap();
+        let params = [("action", "approve"), ("interaction_id", interaction_id)];
+        let response = testing::post_form(&state, "/consent", &params).await;
+        assert!(
+            response.redirect_param("code").is_some(),
+            "{}",
+            response.body
+        );
+        // A new session of the same user does not ask again
+        let response = authorize(&state, &[], None).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.redirect_param("code").is_some());
+    }
+
+    #[tokio::test]
+    async fn prompt_none_with_expired_session() {
+        let state = testing::state(&[("SESSION_EXPIRATION", "0")]);
+        let cookie = session_cookie(&authorize(&state, &[], None).await);
+        let response = authorize(&state, &[("prompt", "none")], Some(&cookie)).await;
+        assert_eq!(response.redirect_param("error").unwrap(), "login_required");
+    }
 }
@@ -1,2 +1,3 @@
 pub mod extractor;
 pub mod handler;
+pub mod request_object;
@@ -0,0 +1,299 @@
+use super::extractor::{AuthorizeParams, RequestObject, RequestObjectParams};
+use crate::bounded_string::SecureString;
+use crate::data::ErrorResponse;
+use crate::state::client::Client;
+use crate::state::AppState;
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation};
+use serde_json::{Map, Value};
+use std::time::Duration;
+use thiserror::Error;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+// Far above the size of a signed request object
+const MAX_REQUEST_OBJECT_LENGTH: usize = 64 * 1024;
+
+// https://datatracker.ietf.org/doc/html/rfc9101#section-6
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("request object must be signed")]
+    Unsigned,
+    #[error("request object signature algorithm must be `{0:?}`: found `{1:?}`")]
+    Algorithm(Algorithm, Algorithm),
+    #[error("no key of client `{0}` can verify the request object")]
+    MissingKey(SecureString),
+    #[error("invalid request object: {0}")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("request object claim `{0}` does not match: expected `{1}` got `{2}`")]
+    Claim(&'static str, String, Value),
+    #[error("request object claim `{0}` is missing")]
+    MissingClaim(&'static str),
+    #[error("invalid request object parameters: {0}")]
+    InvalidParam(#[from] serde_json::Error),
+    #[error("`request_uri` `{0}` is not registered for client `{1}`")]
+    RequestUri(String, SecureString),
+    #[error("request object cannot be retrieved: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[error("request object is longer than {0} bytes")]
+    TooLong(usize),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let error = match self {
+            Self::RequestUri(..) | Self::Fetch(_) | Self::TooLong(_) => "invalid_request_uri",
+            _ => "invalid_request_object",
+        };
+        let response = ErrorResponse::new(error, self);
+        (StatusCode::BAD_REQUEST, Json(response)).into_response()
+    }
+}
+
+pub async fn resolve(
+    state: &AppState,
+    params: RequestObjectParams,
+) -> Result<AuthorizeParams, Error> {
+    let client = state.client(&params.client_id);
+    let jwt = match params.request {
+        RequestObject::Value(jwt) => jwt,
+        RequestObject::Reference(uri) => fetch(&client, uri).await?,
+    };
+    let claims = verify(state, &client, &jwt)?;
+    let client_id = client.client_id.to_string();
+    let issuer = state.issuer().to_string();
+    verify_claim(&claims, "client_id", &client_id, |v| v == client_id)?;
+    verify_claim(&claims, "iss", &client_id, |v| v == client_id)?;
+    // The issuer uri is normalized with a trailing slash that clients may omit
+    verify_claim(&claims, "aud", &issuer, |v| {
+        v.trim_end_matches('/') == issuer.trim_end_matches('/')
+    })?;
+    // The request object claims take precedence over the query parameters
+    let mut merged: Map<String, Value> = params
+        .query
+        .into_iter()
+        .map(|(k, v)| (k, Value::String(v)))
+        .collect();
+    merged.extend(claims);
+    Ok(serde_json::from_value(Value::Object(merged))?)
+}
+
+// Only the URIs registered by the client are fetched
+// https://openid.net/specs/openid-connect-core-1_0.html#RequestUriParameter
+async fn fetch(client: &Client, uri: String) -> Result<String, Error> {
+    if !uri
+        .parse::<Uri>()
+        .is_ok_and(|uri| client.request_uris.contains(&uri))
+    {
+        return Err(Error::RequestUri(uri, client.client_id.clone()));
+    }
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let mut response = client.get(uri).send().await?.error_for_status()?;
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > MAX_REQUEST_OBJECT_LENGTH {
+            return Err(Error::TooLong(MAX_REQUEST_OBJECT_LENGTH));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(String::from_utf8_lossy(&body).trim().into())
+}
+
+fn verify(state: &AppState, client: &Client, jwt: &str) -> Result<Map<String, Value>, Error> {
+    if is_unsigned(jwt) {
+        return Err(Error::Unsigned);
+    }
+    let header = jsonwebtoken::decode_header(jwt)?;
+    if let Some(expected) = client.request_object_signing_alg {
+        if expected != header.alg {
+            return Err(Error::Algorithm(expected, header.alg));
+        }
+    }
+    let key = decoding_key(state, client, &header)?;
+    let mut validation = Validation::new(header.alg);
+    validation.required_spec_claims.clear();
+    validation.validate_aud = false;
+    Ok(jsonwebtoken::decode(jwt, &key, &validation)?.claims)
+}
+
+fn is_unsigned(jwt: &str) -> bool {
+    jwt.split('.')
+        .next()
+        .and_then(|header| URL_SAFE_NO_PAD.decode(header).ok())
+        .and_then(|header| serde_json::from_slice::<Value>(&header).ok())
+        .is_some_and(|header| header["alg"] == "none")
+}
+
+fn decoding_key(state: &AppState, client: &Client, header: &Header) -> Result<DecodingKey, Error> {
+    let missing_key = || Error::MissingKey(client.client_id.clone());
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        let secret = state
+            .client_secret(&client.client_id)
+            .ok_or_else(missing_key)?;
+        return Ok(DecodingKey::from_secret(secret.to_string().as_bytes()));
+    }
+    let jwks = client.jwks.as_ref().ok_or_else(missing_key)?;
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None => jwks.keys.first(),
+    };
+    Ok(DecodingKey::from_jwk(jwk.ok_or_else(missing_key)?)?)
+}
+
+fn verify_claim(
+    claims: &Map<String, Value>,
+    name: &'static str,
+    expected: &str,
+    predicate: impl Fn(&str) -> bool,
+) -> Result<(), Error> {
+    let value = claims.get(name).ok_or(Error::MissingClaim(name))?;
+    let valid = match value {
+        Value::String(value) => predicate(value),
+        Value::Array(values) => values.iter().filter_map(Value::as_str).any(predicate),
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::Claim(name, expected.into(), value.clone()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MAX_REQUEST_OBJECT_LENGTH;
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET, CODE_CHALLENGE, REDIRECT_URI};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::{json, Value};
+    use tokio::net::TcpListener;
+
+    fn request_object(claims: &Value) -> String {
+        let key = EncodingKey::from_secret(CLIENT_SECRET.as_bytes());
+        jsonwebtoken::encode(&Header::default(), claims, &key).unwrap()
+    }
+
+    fn claims() -> Value {
+        json!({
+            "aud": "https://login.helloprima.com",
+            "client_id": CLIENT_ID,
+            "code_challenge": CODE_CHALLENGE,
+            "code_challenge_method": "S256",
+            "iss": CLIENT_ID,
+            "redirect_uri": REDIRECT_URI,
+            "response_mode": "query",
+            "response_type": "code",
+            "scope": "openid",
+            "state": "12345678901234567890",
+        })
+    }
+
+    async fn authorize(
+        state: &crate::state::AppState,
+        name: &str,
+        value: &str,
+    ) -> testing::TestResponse {
+        let query = serde_urlencoded::to_string([("client_id", CLIENT_ID), (name, value)]).unwrap();
+        testing::get(state, &format!("/authorize?{query}")).await
+    }
+
+    #[tokio::test]
+    async fn request_object_authorizes() {
+        let state = testing::state(&[]);
+        let response = authorize(&state, "request", &request_object(&claims())).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.redirect_param("code").is_some());
+    }
+
+    #[tokio::test]
+    async fn missing_claims_rejected() {
+        let state = testing::state(&[]);
+        for name in ["aud", "client_id", "iss"] {
+            let mut claims = claims();
+            claims.as_object_mut().unwrap().remove(name);
+            let response = authorize(&state, "request", &request_object(&claims)).await;
+            assert_eq!(response.status, StatusCode::BAD_REQUEST, "{name}");
+            assert_eq!(response.json()["error"], "invalid_request_object");
+        }
+    }
+
+    #[tokio::test]
+    async fn unregistered_request_uri_rejected() {
+        let state = testing::state(&[]);
+        let response = authorize(&state, "request_uri", "http://169.254.169.254/latest").await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+        assert_eq!(response.json()["error"], "invalid_request_uri");
+    }
+
+    // Serves the request object and an oversized one from a local listener
+    async fn request_uris() -> (String, String) {
+        let request_object = request_object(&claims());
+        let oversized = "a".repeat(MAX_REQUEST_OBJECT_LENGTH + 1);
+        let router = Router::new()
+            .route("/request", get(|| async { request_object }))
+            .route("/oversized", get(|| async { oversized }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (
+            format!("http://{address}/request"),
+            format!("http://{address}/oversized"),
+        )
+    }
+
+    #[tokio::test]
+    async fn request_uri_fetched() {
+        let (request_uri, oversized_uri) = request_uris().await;
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            (
+                "CLIENTS__WEB__REQUEST_URIS",
+                &format!("{request_uri} {oversized_uri}"),
+            ),
+        ]);
+        let response = authorize(&state, "request_uri", &request_uri).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.redirect_param("code").is_some());
+        let response = authorize(&state, "request_uri", &oversized_uri).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_request_uri");
+        assert!(response.json()["error_description"]
+            .as_str()
+            .unwrap()
+            .contains("longer than"));
+    }
+
+    #[tokio::test]
+    async fn pushed_authorization_required() {
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            (
+                "CLIENTS__WEB__REQUIRE_PUSHED_AUTHORIZATION_REQUESTS",
+                "true",
+            ),
+        ]);
+        let response = authorize(&state, "request", &request_object(&claims())).await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+        assert!(response.body.contains("must push"));
+    }
+}
@@ -44,6 +44,14 @@ impl<const L: usize, const U: usize> TryFrom<String> for BoundedString<L, U> {
     }
 }
 
+impl<const L: usize, const U: usize> std::str::FromStr for BoundedString<L, U> {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value.try_into()
+    }
+}
+
 pub type NonEmptyString = BoundedString<1, { usize::MAX }>;
 pub type SecureString = BoundedString<SECURE_LENGTH, { usize::MAX }>;
 
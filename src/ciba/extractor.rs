@@ -0,0 +1,89 @@
+use crate::authorize::extractor::scope;
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::data::{Action, Scope};
+use axum::extract::{Form, FromRequest};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_request
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct BackchannelAuthenticationParams {
+    #[serde(default, deserialize_with = "binding_message")]
+    pub binding_message: Option<NonEmptyString>,
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(default, deserialize_with = "client_notification_token")]
+    pub client_notification_token: Option<NonEmptyString>,
+    #[serde(default, deserialize_with = "client_secret")]
+    pub client_secret: Option<SecureString>,
+    #[serde(deserialize_with = "login_hint")]
+    pub login_hint: NonEmptyString,
+    #[serde(default, deserialize_with = "requested_expiry")]
+    pub requested_expiry: Option<u64>,
+    #[serde(deserialize_with = "scope")]
+    pub scope: HashSet<Scope>,
+}
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct ResolutionParams {
+    #[serde(deserialize_with = "action")]
+    pub action: Action,
+    #[serde(deserialize_with = "auth_req_id")]
+    pub auth_req_id: SecureString,
+}
+
+fn action<'de, D: Deserializer<'de>>(d: D) -> Result<Action, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `action`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn auth_req_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `auth_req_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn binding_message<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NonEmptyString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `binding_message`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn client_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn client_notification_token<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<NonEmptyString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_notification_token`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn client_secret<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecureString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_secret`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn login_hint<'de, D: Deserializer<'de>>(d: D) -> Result<NonEmptyString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `login_hint`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn requested_expiry<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `requested_expiry`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
@@ -0,0 +1,292 @@
+use super::extractor::{BackchannelAuthenticationParams, ResolutionParams};
+use super::ping;
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::data::time::UnixTime;
+use crate::data::{Action, ErrorResponse};
+use crate::mtls::ClientCertificate;
+use crate::state::ciba::BackchannelRequest;
+use crate::state::client::BackchannelTokenDeliveryMode;
+use crate::state::device::PendingGrant;
+use crate::state::{AppState, Grant};
+use crate::token::handler::{authenticate_client, InvalidParamError};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response, Result};
+use axum::{Extension, Json};
+use serde::Serialize;
+use thiserror::Error;
+
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#successful_authentication_request_acknowdlegment
+#[derive(Serialize)]
+pub struct BackchannelAuthenticationResponse {
+    auth_req_id: SecureString,
+    expires_in: u64,
+    interval: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidClient(InvalidParamError),
+    #[error("client `{0}` is not registered with a backchannel token delivery mode")]
+    UnauthorizedClient(SecureString),
+    #[error("`client_notification_token` is required with the ping delivery mode")]
+    MissingNotificationToken,
+    #[error("no user is identified by `{0}`")]
+    UnknownUser(NonEmptyString),
+}
+
+impl Error {
+    // https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_error_response
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidClient(_) => "invalid_client",
+            Self::UnauthorizedClient(_) => "unauthorized_client",
+            Self::MissingNotificationToken => "invalid_request",
+            Self::UnknownUser(_) => "unknown_user_id",
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidClient(_) => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(ErrorResponse::new(self.code(), &self))).into_response()
+    }
+}
+
+pub async fn backchannel_authentication(
+    state: AppState,
+    certificate: Option<Extension<ClientCertificate>>,
+    params: BackchannelAuthenticationParams,
+) -> Result<Json<BackchannelAuthenticationResponse>> {
+    let certificate = certificate
+        .as_ref()
+        .map(|Extension(certificate)| certificate);
+    let client = authenticate_client(&state, &params.client_id, params.client_secret, certificate)
+        .map_err(Error::InvalidClient)?;
+    let mode = client
+        .backchannel_token_delivery_mode
+        .ok_or(Error::UnauthorizedClient(client.client_id.clone()))?;
+    if mode == BackchannelTokenDeliveryMode::Ping && params.client_notification_token.is_none() {
+        return Err(Error::MissingNotificationToken.into());
+    }
+    // The only user is identified by its username or email
+    if !state.get_user().identified_by(&params.login_hint) {
+        return Err(Error::UnknownUser(params.login_hint.clone()).into());
+    }
+    let grant = Grant {
+        auth_time: UnixTime::now(),
+        authentication: state.authentication(),
+        authorization_details: Vec::new(),
+        client_id: client.client_id,
+        nonce: None,
+        resource: Vec::new(),
+        scope: params.scope,
+        sid: None,
+        user_id: state.get_user().id(),
+    };
+    let auth_req_id = state.gen_secure_string();
+    // The client may only shorten the expiration
+    let expires_in = params
+        .requested_expiry
+        .map_or(state.ciba_expiration(), |expiry| {
+            expiry.min(state.ciba_expiration())
+        });
+    let request = BackchannelRequest {
+        binding_message: params.binding_message,
+        client_notification_token: params.client_notification_token,
+        login_hint: params.login_hint,
+        pending: PendingGrant::new(grant, expires_in, state.ciba_interval()),
+    };
+    state.set_backchannel_request(auth_req_id.clone(), request);
+    Ok(Json(BackchannelAuthenticationResponse {
+        auth_req_id,
+        expires_in,
+        interval: state.ciba_interval(),
+    }))
+}
+
+// Test hooks standing for the user on their authentication device
+pub async fn pending(state: AppState) -> impl IntoResponse {
+    Json(state.pending_backchannel_requests())
+}
+
+pub async fn resolve(state: AppState, params: ResolutionParams) -> impl IntoResponse {
+    let approved = matches!(params.action, Action::Approve);
+    let auth_req_id = params.auth_req_id;
+    let Some((client_id, token)) = state.resolve_backchannel_request(&auth_req_id, approved) else {
+        let message = format!("no pending backchannel request associated with `{auth_req_id}`");
+        return (StatusCode::BAD_REQUEST, message);
+    };
+    let outcome = if approved { "approved" } else { "denied" };
+    let client = state.client(&client_id);
+    if client.backchannel_token_delivery_mode == Some(BackchannelTokenDeliveryMode::Ping) {
+        if let Some((endpoint, token)) = client.backchannel_client_notification_endpoint.zip(token)
+        {
+            match ping::notification(endpoint, token, auth_req_id) {
+                Ok(notification) => {
+                    tokio::spawn(ping::notify(notification));
+                }
+                Err(e) => {
+                    let message =
+                        format!("backchannel request {outcome} but the client was not pinged: {e}");
+                    return (StatusCode::INTERNAL_SERVER_ERROR, message);
+                }
+            }
+        }
+    }
+    (StatusCode::OK, format!("backchannel request {outcome}"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::AppState;
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET};
+    use axum::http::header::AUTHORIZATION;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::Value;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    const NOTIFICATION_TOKEN: &str = "8d67dc78-7faa-4d41-aabd-67707b374255";
+
+    fn state(overrides: &[(&str, &str)]) -> AppState {
+        let defaults = [
+            ("CIBA_INTERVAL", "0"),
+            ("CLIENTS__WEB__BACKCHANNEL_TOKEN_DELIVERY_MODE", "poll"),
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+        ];
+        testing::state_with(&defaults, overrides)
+    }
+
+    async fn bc_authorize(state: &AppState, params: &[(&str, &str)]) -> testing::TestResponse {
+        let defaults = [
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("login_hint", "johnny"),
+            ("scope", "openid"),
+        ];
+        testing::post_form(state, "/bc-authorize", &testing::merge(&defaults, params)).await
+    }
+
+    async fn auth_req_id(state: &AppState, params: &[(&str, &str)]) -> String {
+        let response = bc_authorize(state, params).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        response.json()["auth_req_id"].as_str().unwrap().to_string()
+    }
+
+    async fn resolve(state: &AppState, auth_req_id: &str, action: &str) -> testing::TestResponse {
+        let params = [("action", action), ("auth_req_id", auth_req_id)];
+        testing::post_form(state, "/admin/ciba", &params).await
+    }
+
+    async fn poll(state: &AppState, client_id: &str, auth_req_id: &str) -> testing::TestResponse {
+        let params = [
+            ("grant_type", "urn:openid:params:grant-type:ciba"),
+            ("auth_req_id", auth_req_id),
+            ("client_id", client_id),
+            ("client_secret", CLIENT_SECRET),
+        ];
+        testing::post_form(state, "/token", &params).await
+    }
+
+    #[tokio::test]
+    async fn poll_until_approved() {
+        let state = state(&[]);
+        let auth_req_id = auth_req_id(&state, &[]).await;
+        let response = poll(&state, CLIENT_ID, &auth_req_id).await;
+        assert_eq!(response.json()["error"], "authorization_pending");
+        let response = resolve(&state, &auth_req_id, "approve").await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let response = poll(&state, CLIENT_ID, &auth_req_id).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.json()["access_token"].is_string());
+        // The grant can only be redeemed once
+        let response = poll(&state, CLIENT_ID, &auth_req_id).await;
+        assert_eq!(response.json()["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn denied() {
+        let state = state(&[]);
+        let auth_req_id = auth_req_id(&state, &[]).await;
+        resolve(&state, &auth_req_id, "deny").await;
+        let response = poll(&state, CLIENT_ID, &auth_req_id).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "access_denied");
+    }
+
+    #[tokio::test]
+    async fn polled_by_another_client() {
+        let state = state(&[("CLIENTS__OTHER__CLIENT_ID", "otherClientIdentifier")]);
+        let auth_req_id = auth_req_id(&state, &[]).await;
+        resolve(&state, &auth_req_id, "approve").await;
+        let response = poll(&state, "otherClientIdentifier", &auth_req_id).await;
+        assert_eq!(response.json()["error"], "invalid_grant");
+        let response = poll(&state, CLIENT_ID, &auth_req_id).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    #[tokio::test]
+    async fn unknown_user() {
+        let state = state(&[]);
+        let response = bc_authorize(&state, &[("login_hint", "jane")]).await;
+        assert_eq!(response.json()["error"], "unknown_user_id");
+    }
+
+    #[tokio::test]
+    async fn requested_expiry_bounded() {
+        let state = state(&[("CIBA_EXPIRATION", "120")]);
+        let response = bc_authorize(&state, &[("requested_expiry", "60")]).await;
+        assert_eq!(response.json()["expires_in"], 60);
+        let response = bc_authorize(&state, &[("requested_expiry", "31536000")]).await;
+        assert_eq!(response.json()["expires_in"], 120);
+    }
+
+    #[tokio::test]
+    async fn expired_requests_purged() {
+        let state = state(&[("CIBA_EXPIRATION", "0")]);
+        let denied = auth_req_id(&state, &[]).await;
+        resolve(&state, &denied, "deny").await;
+        auth_req_id(&state, &[]).await;
+        let response = poll(&state, CLIENT_ID, &denied).await;
+        assert_eq!(response.json()["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn ping() {
+        let (sender, mut receiver) = mpsc::channel(1);
+        let endpoint = Router::new().route(
+            "/ping",
+            post(|headers: HeaderMap, Json(body): Json<Value>| async move {
+                let authorization = headers[AUTHORIZATION].to_str().unwrap().to_string();
+                sender.send((authorization, body)).await.unwrap();
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint_uri = format!("http://{}/ping", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, endpoint).await });
+        let state = state(&[
+            ("CLIENTS__WEB__BACKCHANNEL_TOKEN_DELIVERY_MODE", "ping"),
+            (
+                "CLIENTS__WEB__BACKCHANNEL_CLIENT_NOTIFICATION_ENDPOINT",
+                &endpoint_uri,
+            ),
+        ]);
+        let response = bc_authorize(&state, &[]).await;
+        assert_eq!(response.json()["error"], "invalid_request");
+        let params = [("client_notification_token", NOTIFICATION_TOKEN)];
+        let auth_req_id = auth_req_id(&state, &params).await;
+        resolve(&state, &auth_req_id, "approve").await;
+        let notification = tokio::time::timeout(Duration::from_secs(5), receiver.recv());
+        let (authorization, body) = notification.await.unwrap().unwrap();
+        assert_eq!(authorization, format!("Bearer {NOTIFICATION_TOKEN}"));
+        assert_eq!(body["auth_req_id"], auth_req_id);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod extractor;
+pub mod handler;
+pub mod ping;
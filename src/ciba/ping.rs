@@ -0,0 +1,28 @@
+use crate::bounded_string::{NonEmptyString, SecureString};
+use axum::http::Uri;
+use reqwest::RequestBuilder;
+use serde_json::json;
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tells the client that the result of the request is ready to be polled
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#ping_callback
+pub fn notification(
+    endpoint: Uri,
+    token: NonEmptyString,
+    auth_req_id: SecureString,
+) -> reqwest::Result<RequestBuilder> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    Ok(client
+        .post(endpoint.to_string())
+        .bearer_auth(token)
+        .json(&json!({ "auth_req_id": auth_req_id })))
+}
+
+// The notification is best effort: the client can still poll without it
+pub async fn notify(notification: RequestBuilder) {
+    let _ = notification.send().await;
+}
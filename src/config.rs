@@ -1,29 +1,76 @@
+use crate::bounded_string::NonEmptyString;
+use crate::data::access_token::{AccessTokenFormat, AccessTokenProfile};
+use crate::data::authorization_details::AuthorizationDetailsType;
+use crate::state::client::Client;
+use crate::state::resource::ResourceServer;
+use crate::state::session::Authentication;
 use crate::state::User;
 use crate::{bounded_string::SecureString, data::Secret};
 use axum::http::Uri;
 use config::{Config, Environment};
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 #[derive(Deserialize)]
 pub struct Configuration {
+    pub access_token_format: AccessTokenFormat,
+    pub access_token_profile: AccessTokenProfile,
     #[serde(with = "http_serde::uri")]
     pub audience: Uri,
+    pub authentication: Authentication,
+    #[serde(default)]
+    pub authorization_details_types: HashMap<String, AuthorizationDetailsType>,
+    pub backchannel_logout_attempts: u32,
+    pub ciba_expiration: u64,
+    pub ciba_interval: u64,
     pub client_secret: SecureString,
+    #[serde(default)]
+    pub clients: HashMap<String, Client>,
+    pub consent_required: bool,
+    pub device_expiration: u64,
+    pub device_interval: u64,
+    pub dpop_nonce_expiration: u64,
+    pub dpop_nonce_required: bool,
     pub expiration: u64,
     #[serde(with = "http_serde::uri")]
     pub issuer: Uri,
+    pub jarm_expiration: u64,
+    pub key_rotation_interval: u64,
+    pub otp_acr: NonEmptyString,
+    pub otp_attempts: u32,
+    pub otp_expiration: u64,
+    pub par_expiration: u64,
     pub port: u16,
+    #[serde(default)]
+    pub resources: HashMap<String, ResourceServer>,
     pub rng_seed: u64,
     pub secret: Secret,
+    pub session_expiration: u64,
+    #[serde(default)]
+    pub tls_certificate: Option<PathBuf>,
+    #[serde(default)]
+    pub tls_client_ca: Option<PathBuf>,
+    pub tls_port: u16,
+    #[serde(default)]
+    pub tls_private_key: Option<PathBuf>,
     pub user: User,
+    #[serde(default)]
+    pub userinfo_acr_values: Vec<NonEmptyString>,
 }
 
 impl Configuration {
     pub fn new() -> Self {
-        let env = Environment::default()
+        Self::from_environment(Environment::default())
+    }
+
+    fn from_environment(env: Environment) -> Self {
+        let env = env
             .separator("__")
             .list_separator(",")
+            .with_list_parse_key("authentication.amr")
             .with_list_parse_key("user.groups")
+            .with_list_parse_key("userinfo_acr_values")
             .try_parsing(true);
         Config::builder()
             .add_source(env)
@@ -33,3 +80,21 @@ impl Configuration {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+impl Configuration {
+    // The defaults of the Dockerfile with some variables overridden
+    pub fn test(overrides: &[(&str, &str)]) -> Self {
+        let mut vars: config::Map<String, String> = include_str!("../Dockerfile")
+            .lines()
+            .filter_map(|line| line.strip_prefix("ENV ")?.split_once('='))
+            .map(|(name, value)| (name.to_string(), value.trim_matches('"').to_string()))
+            .collect();
+        vars.extend(
+            overrides
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string())),
+        );
+        Self::from_environment(Environment::default().source(Some(vars)))
+    }
+}
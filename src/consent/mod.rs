@@ -0,0 +1,108 @@
+use crate::authorize::extractor::AuthorizeParams;
+use crate::authorize::handler;
+use crate::bounded_string::SecureString;
+use crate::data::{Action, Scope};
+use crate::state::consent::Consent;
+use crate::state::{AppState, Grant, Interaction};
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::extract::{Form, FromRequest};
+use axum::http::StatusCode;
+use axum::response::{Response, Result};
+use serde::{Deserialize, Deserializer};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Template)]
+#[template(path = "consent.html")]
+pub struct ConsentPage {
+    client_id: SecureString,
+    interaction_id: SecureString,
+    scopes: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct ConsentParams {
+    #[serde(deserialize_with = "action")]
+    pub action: Action,
+    #[serde(deserialize_with = "interaction_id")]
+    pub interaction_id: SecureString,
+    // The checkboxes of the scopes left selected by the user
+    #[serde(flatten)]
+    pub scopes: HashMap<String, String>,
+}
+
+// Pauses the authorization request until the user reviews the requested scopes
+pub fn challenge(state: &AppState, grant: Grant, params: AuthorizeParams) -> Response {
+    let mut scopes: Vec<(String, String)> = grant
+        .scope
+        .iter()
+        .map(|scope| (scope.to_string().to_lowercase(), scope.claims().join(", ")))
+        .collect();
+    scopes.sort();
+    let page = ConsentPage {
+        client_id: grant.client_id.clone(),
+        interaction_id: state.gen_secure_string(),
+        scopes,
+    };
+    let interaction = Interaction::Consent {
+        grant: Box::new(grant),
+        params,
+    };
+    state.set_interaction(page.interaction_id.clone(), interaction);
+    page.into_response()
+}
+
+pub async fn consent(state: AppState, params: ConsentParams) -> Result<Response> {
+    let Some(Interaction::Consent {
+        mut grant,
+        params: request,
+    }) = state.get_interaction(&params.interaction_id)
+    else {
+        let message = format!(
+            "no pending authorization associated with `{}`",
+            params.interaction_id
+        );
+        return Ok((StatusCode::BAD_REQUEST, message).into_response());
+    };
+    if matches!(params.action, Action::Deny) {
+        let description = "the user denied the consent";
+        return Ok(handler::error(
+            &state,
+            request,
+            "access_denied",
+            description,
+        )?);
+    }
+    // The `openid` scope can not be deselected
+    let granted: HashSet<Scope> = grant
+        .scope
+        .iter()
+        .filter(|scope| {
+            **scope == Scope::Openid
+                || params
+                    .scopes
+                    .contains_key(&scope.to_string().to_lowercase())
+        })
+        .cloned()
+        .collect();
+    let consent = Consent {
+        granted: granted.clone(),
+        requested: grant.scope.clone(),
+    };
+    state.set_consent(&grant.user_id, &grant.client_id, consent);
+    grant.scope = granted;
+    Ok(handler::complete(&state, request, *grant)?)
+}
+
+fn action<'de, D: Deserializer<'de>>(d: D) -> Result<Action, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `action`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn interaction_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `interaction_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,276 @@
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use derive_more::derive::Display;
+use jsonwebtoken::jwk::{AlgorithmParameters, Jwk, JwkSet, PublicKeyUse};
+use p256::ecdh::EphemeralSecret;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use p256::{EncodedPoint, PublicKey};
+use rand::Rng;
+use rsa::{BigUint, Oaep, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+const TAG_LENGTH: usize = 16;
+
+// https://datatracker.ietf.org/doc/html/rfc7518#section-4.1
+#[derive(Clone, Copy, Debug, Deserialize, Display, PartialEq, Serialize)]
+pub enum KeyManagementAlgorithm {
+    #[display("RSA-OAEP")]
+    #[serde(rename = "RSA-OAEP")]
+    RsaOaep,
+    #[display("RSA-OAEP-256")]
+    #[serde(rename = "RSA-OAEP-256")]
+    RsaOaep256,
+    #[display("ECDH-ES")]
+    #[serde(rename = "ECDH-ES")]
+    EcdhEs,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7518#section-5.1
+#[derive(Clone, Copy, Debug, Default, Deserialize, Display, PartialEq, Serialize)]
+pub enum ContentEncryptionAlgorithm {
+    #[default]
+    A256GCM,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no public key compatible with `{0}`")]
+    MissingKey(KeyManagementAlgorithm),
+    #[error("invalid public key: {0}")]
+    InvalidKey(String),
+    #[error("encryption failed: {0}")]
+    Encryption(String),
+}
+
+#[derive(Serialize)]
+struct Header<'a> {
+    alg: KeyManagementAlgorithm,
+    enc: ContentEncryptionAlgorithm,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cty: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    epk: Option<EphemeralKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kid: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct EphemeralKey {
+    kty: &'static str,
+    crv: &'static str,
+    x: String,
+    y: String,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7516#section-7.1
+pub fn encrypt(
+    plaintext: &[u8],
+    jwks: &JwkSet,
+    alg: KeyManagementAlgorithm,
+    enc: ContentEncryptionAlgorithm,
+    cty: Option<&'static str>,
+) -> Result<String, Error> {
+    let jwk = find_key(jwks, alg)?;
+    let mut rng = rand::thread_rng();
+    let (cek, encrypted_key, epk) = match alg {
+        KeyManagementAlgorithm::RsaOaep | KeyManagementAlgorithm::RsaOaep256 => {
+            let cek: [u8; 32] = rng.gen();
+            let key = rsa_key(jwk)?;
+            let encrypted_key = if alg == KeyManagementAlgorithm::RsaOaep {
+                key.encrypt(&mut rng, Oaep::new::<Sha1>(), &cek)
+            } else {
+                key.encrypt(&mut rng, Oaep::new::<Sha256>(), &cek)
+            };
+            let encrypted_key = encrypted_key.map_err(|e| Error::Encryption(e.to_string()))?;
+            (cek, encrypted_key, None)
+        }
+        KeyManagementAlgorithm::EcdhEs => {
+            let secret = EphemeralSecret::random(&mut rng);
+            let shared = secret.diffie_hellman(&ec_key(jwk)?);
+            let cek = concat_kdf(shared.raw_secret_bytes(), &enc.to_string());
+            (cek, Vec::new(), Some(ephemeral_key(&secret.public_key())))
+        }
+    };
+    let header = Header {
+        alg,
+        enc,
+        cty,
+        epk,
+        kid: jwk.common.key_id.as_deref(),
+    };
+    let protected = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header).unwrap());
+    let iv: [u8; 12] = rng.gen();
+    let payload = Payload {
+        msg: plaintext,
+        aad: protected.as_bytes(),
+    };
+    let mut ciphertext = Aes256Gcm::new_from_slice(&cek)
+        .unwrap()
+        .encrypt(Nonce::from_slice(&iv), payload)
+        .map_err(|e| Error::Encryption(e.to_string()))?;
+    let tag = ciphertext.split_off(ciphertext.len() - TAG_LENGTH);
+    Ok([
+        protected,
+        URL_SAFE_NO_PAD.encode(encrypted_key),
+        URL_SAFE_NO_PAD.encode(iv),
+        URL_SAFE_NO_PAD.encode(ciphertext),
+        URL_SAFE_NO_PAD.encode(tag),
+    ]
+    .join("."))
+}
+
+fn find_key(jwks: &JwkSet, alg: KeyManagementAlgorithm) -> Result<&Jwk, Error> {
+    jwks.keys
+        .iter()
+        .filter(|jwk| jwk.common.public_key_use != Some(PublicKeyUse::Signature))
+        .find(|jwk| {
+            matches!(
+                (&jwk.algorithm, alg),
+                (
+                    AlgorithmParameters::EllipticCurve(_),
+                    KeyManagementAlgorithm::EcdhEs
+                ) | (AlgorithmParameters::RSA(_), KeyManagementAlgorithm::RsaOaep)
+                    | (
+                        AlgorithmParameters::RSA(_),
+                        KeyManagementAlgorithm::RsaOaep256
+                    )
+            )
+        })
+        .ok_or(Error::MissingKey(alg))
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, Error> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|e| Error::InvalidKey(e.to_string()))
+}
+
+fn rsa_key(jwk: &Jwk) -> Result<RsaPublicKey, Error> {
+    let AlgorithmParameters::RSA(params) = &jwk.algorithm else {
+        return Err(Error::InvalidKey("expected an RSA key".into()));
+    };
+    let n = BigUint::from_bytes_be(&decode(&params.n)?);
+    let e = BigUint::from_bytes_be(&decode(&params.e)?);
+    RsaPublicKey::new(n, e).map_err(|e| Error::InvalidKey(e.to_string()))
+}
+
+fn ec_key(jwk: &Jwk) -> Result<PublicKey, Error> {
+    let AlgorithmParameters::EllipticCurve(params) = &jwk.algorithm else {
+        return Err(Error::InvalidKey("expected an EC key".into()));
+    };
+    let x = decode(&params.x)?;
+    let y = decode(&params.y)?;
+    if x.len() != 32 || y.len() != 32 {
+        return Err(Error::InvalidKey("expected a P-256 key".into()));
+    }
+    let point = EncodedPoint::from_affine_coordinates(x[..].into(), y[..].into(), false);
+    PublicKey::from_sec1_bytes(point.as_bytes()).map_err(|e| Error::InvalidKey(e.to_string()))
+}
+
+fn ephemeral_key(key: &PublicKey) -> EphemeralKey {
+    let point = key.to_encoded_point(false);
+    EphemeralKey {
+        kty: "EC",
+        crv: "P-256",
+        x: URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+        y: URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7518#section-4.6.2
+fn concat_kdf(shared_secret: &[u8], enc: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(1u32.to_be_bytes());
+    hasher.update(shared_secret);
+    hasher.update((enc.len() as u32).to_be_bytes());
+    hasher.update(enc.as_bytes());
+    hasher.update(0u32.to_be_bytes());
+    hasher.update(0u32.to_be_bytes());
+    hasher.update(256u32.to_be_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use p256::SecretKey;
+    use rsa::traits::PublicKeyParts;
+    use rsa::RsaPrivateKey;
+
+    fn jwks(jwk: serde_json::Value) -> JwkSet {
+        serde_json::from_value(serde_json::json!({ "keys": [jwk] })).unwrap()
+    }
+
+    fn open(jwe: &str, cek: &[u8]) -> Vec<u8> {
+        let parts: Vec<Vec<u8>> = jwe
+            .split('.')
+            .map(|p| URL_SAFE_NO_PAD.decode(p).unwrap())
+            .collect();
+        let aad = jwe.split('.').next().unwrap().as_bytes();
+        let msg = [parts[3].as_slice(), parts[4].as_slice()].concat();
+        let payload = Payload { msg: &msg, aad };
+        Aes256Gcm::new_from_slice(cek)
+            .unwrap()
+            .decrypt(Nonce::from_slice(&parts[2]), payload)
+            .unwrap()
+    }
+
+    #[test]
+    fn rsa_oaep_round_trip() {
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), 1024).unwrap();
+        let jwks = jwks(serde_json::json!({
+            "kty": "RSA",
+            "n": URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+            "e": URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+        }));
+        let alg = KeyManagementAlgorithm::RsaOaep;
+        let jwe = encrypt(b"claims", &jwks, alg, Default::default(), None).unwrap();
+        let encrypted_key = URL_SAFE_NO_PAD
+            .decode(jwe.split('.').nth(1).unwrap())
+            .unwrap();
+        let cek = key.decrypt(Oaep::new::<Sha1>(), &encrypted_key).unwrap();
+        assert_eq!(open(&jwe, &cek), b"claims")
+    }
+
+    #[test]
+    fn ecdh_es_round_trip() {
+        let key = SecretKey::random(&mut rand::thread_rng());
+        let point = key.public_key().to_encoded_point(false);
+        let jwks = jwks(serde_json::json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+            "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+        }));
+        let alg = KeyManagementAlgorithm::EcdhEs;
+        let jwe = encrypt(b"claims", &jwks, alg, Default::default(), None).unwrap();
+        let header = URL_SAFE_NO_PAD
+            .decode(jwe.split('.').next().unwrap())
+            .unwrap();
+        let header: serde_json::Value = serde_json::from_slice(&header).unwrap();
+        let x = URL_SAFE_NO_PAD
+            .decode(header["epk"]["x"].as_str().unwrap())
+            .unwrap();
+        let y = URL_SAFE_NO_PAD
+            .decode(header["epk"]["y"].as_str().unwrap())
+            .unwrap();
+        let epk = EncodedPoint::from_affine_coordinates(x[..].into(), y[..].into(), false);
+        let epk = PublicKey::from_sec1_bytes(epk.as_bytes()).unwrap();
+        let shared = p256::ecdh::diffie_hellman(key.to_nonzero_scalar(), epk.as_affine());
+        let cek = concat_kdf(shared.raw_secret_bytes(), "A256GCM");
+        assert_eq!(open(&jwe, &cek), b"claims")
+    }
+
+    #[test]
+    fn missing_key() {
+        let jwks = jwks(serde_json::json!({ "kty": "RSA", "n": "AQAB", "e": "AQAB" }));
+        let alg = KeyManagementAlgorithm::EcdhEs;
+        let result = encrypt(b"claims", &jwks, alg, Default::default(), None);
+        assert!(matches!(result, Err(Error::MissingKey(_))))
+    }
+}
@@ -1,6 +1,10 @@
-use base64::engine::general_purpose::STANDARD;
+pub mod jwe;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
 use base64::Engine;
+use jsonwebtoken::jwk::Jwk;
 use rand::{CryptoRng, Rng};
+use sha2::{Digest, Sha256};
 
 // https://datatracker.ietf.org/doc/html/rfc6749#section-10.10
 pub const SECURE_LENGTH: usize = 20;
@@ -8,3 +12,51 @@ pub const SECURE_LENGTH: usize = 20;
 pub fn strong_random_bytes<R: Rng + CryptoRng>(mut rng: R) -> String {
     STANDARD.encode(rng.gen::<[u8; SECURE_LENGTH]>())
 }
+
+// Random bytes that can be used in URL paths and queries without escaping
+pub fn url_safe_random_bytes<R: Rng + CryptoRng>(mut rng: R) -> String {
+    URL_SAFE_NO_PAD.encode(rng.gen::<[u8; SECURE_LENGTH]>())
+}
+
+// https://openid.net/specs/openid-connect-core-1_0.html#CodeIDToken
+pub fn half_hash(value: &str) -> String {
+    let hash = Sha256::digest(value);
+    let half_length = hash.len() / 2;
+    let half_hash = &hash[..half_length];
+    URL_SAFE_NO_PAD.encode(half_hash)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9449#section-4.2
+pub fn hash(value: impl AsRef<[u8]>) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(value))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7638#section-3
+pub fn jwk_thumbprint(jwk: &Jwk) -> String {
+    // The key parameters hold exactly the required members, which the JSON map
+    // serializes in lexicographic order without whitespace
+    let members = serde_json::to_value(&jwk.algorithm).unwrap();
+    hash(members.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // https://datatracker.ietf.org/doc/html/rfc7638#section-3.1
+    #[test]
+    fn rsa_thumbprint() {
+        let jwk: Jwk = serde_json::from_value(serde_json::json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        }))
+        .unwrap();
+        assert_eq!(
+            jwk_thumbprint(&jwk),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
+}
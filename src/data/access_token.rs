@@ -1,18 +1,30 @@
+use super::authorization_details::AuthorizationDetail;
 use super::time::UnixTime;
-use super::Scope;
-use crate::bounded_string::SecureString;
+use super::{Scope, TokenType};
+use crate::bounded_string::{NonEmptyString, SecureString};
 use crate::state::AppState;
 use axum::http::Uri;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::formats::{PreferOne, SpaceSeparator};
+use serde_with::{serde_as, DeserializeAs, DisplayFromStr, OneOrMany, StringWithSeparator};
 use std::collections::HashSet;
 use thiserror::Error;
 
-#[derive(Deserialize, Serialize)]
+#[serde_as]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct AccessToken {
-    #[serde(with = "http_serde::uri")]
-    pub aud: Uri,
+    pub acr: NonEmptyString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde_as(as = "OneOrMany<DisplayFromStr, PreferOne>")]
+    pub aud: Vec<Uri>,
     pub auth_time: UnixTime,
+    // https://datatracker.ietf.org/doc/html/rfc9396#section-9.1
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorization_details: Vec<AuthorizationDetail>,
     pub cid: SecureString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
     pub exp: UnixTime,
     pub iat: UnixTime,
     #[serde(with = "http_serde::uri")]
@@ -24,10 +36,85 @@ pub struct AccessToken {
     pub ver: u32,
 }
 
+// The claim names of the access tokens: either the ones issued by Okta or the
+// standard JWT profile
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessTokenProfile {
+    #[default]
+    Okta,
+    // https://datatracker.ietf.org/doc/html/rfc9068
+    Rfc9068,
+}
+
+// Whether the access tokens are self-contained JWTs or random references to
+// claims kept by the server, which resource servers have to introspect
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessTokenFormat {
+    #[default]
+    Jwt,
+    Opaque,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9068#section-2.2
+#[serde_as]
+#[derive(Deserialize, Serialize)]
+pub struct Rfc9068AccessToken {
+    pub acr: NonEmptyString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    #[serde_as(as = "OneOrMany<DisplayFromStr, PreferOne>")]
+    pub aud: Vec<Uri>,
+    pub auth_time: UnixTime,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authorization_details: Vec<AuthorizationDetail>,
+    pub client_id: SecureString,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
+    pub exp: UnixTime,
+    pub iat: UnixTime,
+    #[serde(with = "http_serde::uri")]
+    pub iss: Uri,
+    pub jti: SecureString,
+    // https://datatracker.ietf.org/doc/html/rfc9068#section-2.2.3
+    #[serde(deserialize_with = "scope", serialize_with = "lowercase_scope")]
+    pub scope: HashSet<Scope>,
+    pub sub: SecureString,
+}
+
+// Access tokens of either profile
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ProfiledAccessToken {
+    Okta(AccessToken),
+    Rfc9068(Rfc9068AccessToken),
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8693#section-4.1
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Actor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+    pub sub: SecureString,
+}
+
+// The key the access token is bound to
+// https://datatracker.ietf.org/doc/html/rfc7800#section-3.1
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Confirmation {
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-6.1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jkt: Option<String>,
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-3.1
+    #[serde(default, rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("invalid issuer {0} expected {0}")]
-    InvalidIssuer(Uri, Uri),
+    InvalidIssuer(Box<Uri>, Box<Uri>),
     #[error("expired token")]
     Expired,
     #[error("missing scope: {0}")]
@@ -36,10 +123,73 @@ pub enum Error {
     MissingScopes(HashSet<Scope>),
 }
 
+impl From<AccessToken> for Rfc9068AccessToken {
+    fn from(token: AccessToken) -> Self {
+        Self {
+            acr: token.acr,
+            act: token.act,
+            aud: token.aud,
+            auth_time: token.auth_time,
+            authorization_details: token.authorization_details,
+            client_id: token.cid,
+            cnf: token.cnf,
+            exp: token.exp,
+            iat: token.iat,
+            iss: token.iss,
+            jti: token.jti,
+            scope: token.scp,
+            sub: token.sub,
+        }
+    }
+}
+
+impl From<ProfiledAccessToken> for AccessToken {
+    fn from(token: ProfiledAccessToken) -> Self {
+        match token {
+            ProfiledAccessToken::Okta(token) => token,
+            ProfiledAccessToken::Rfc9068(token) => Self {
+                acr: token.acr,
+                act: token.act,
+                aud: token.aud,
+                auth_time: token.auth_time,
+                authorization_details: token.authorization_details,
+                cid: token.client_id,
+                cnf: token.cnf,
+                exp: token.exp,
+                iat: token.iat,
+                iss: token.iss,
+                jti: token.jti,
+                scp: token.scope,
+                uid: token.sub.clone(),
+                sub: token.sub,
+                ver: 1,
+            },
+        }
+    }
+}
+
 impl AccessToken {
+    pub fn token_type(&self) -> TokenType {
+        match &self.cnf {
+            Some(Confirmation { jkt: Some(_), .. }) => TokenType::Dpop,
+            _ => TokenType::Bearer,
+        }
+    }
+
+    pub fn jkt(&self) -> Option<&str> {
+        self.cnf.as_ref()?.jkt.as_deref()
+    }
+
+    pub fn x5t_s256(&self) -> Option<&str> {
+        self.cnf.as_ref()?.x5t_s256.as_deref()
+    }
+
     pub fn validate(&self, state: &AppState) -> Result<(), Error> {
         if self.iss != state.issuer() {
-            return Err(Error::InvalidIssuer(self.iss.clone(), state.issuer()));
+            return Err(Error::InvalidIssuer(
+                Box::new(self.iss.clone()),
+                Box::new(state.issuer()),
+            ));
         }
         if self.exp.expired() {
             return Err(Error::Expired);
@@ -54,8 +204,66 @@ impl AccessToken {
     }
 }
 
+fn scope<'de, D: Deserializer<'de>>(d: D) -> Result<HashSet<Scope>, D::Error> {
+    StringWithSeparator::<SpaceSeparator, Scope>::deserialize_as(d)
+}
+
+// The scopes as they are requested rather than with the Okta capitalization
+fn lowercase_scope<S: Serializer>(scopes: &HashSet<Scope>, s: S) -> Result<S::Ok, S::Error> {
+    let scopes = scopes.iter().map(|scope| scope.to_string().to_lowercase());
+    s.serialize_str(&scopes.collect::<Vec<_>>().join(" "))
+}
+
 fn display(scopes: &HashSet<Scope>) -> String {
     scopes
-        .into_iter()
+        .iter()
         .fold(String::new(), |a, b| a + b.to_string().as_str() + ", ")
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn access_token() -> AccessToken {
+        let now = UnixTime::now();
+        AccessToken {
+            acr: "1".try_into().unwrap(),
+            act: None,
+            aud: vec![Uri::from_static("api.example.com")],
+            auth_time: now,
+            authorization_details: Vec::new(),
+            cid: "0jrabyQWm4B9zVJPbotY".try_into().unwrap(),
+            cnf: None,
+            exp: now + 60,
+            iat: now,
+            iss: Uri::from_static("https://login.helloprima.com"),
+            jti: "m4q4GxAYXSYCOzYQzrfZ9X1J0rM=".try_into().unwrap(),
+            scp: [Scope::Openid].into(),
+            sub: "mmMoPLrw/bzrH2R5sZfzqI3Q2Ak=".try_into().unwrap(),
+            uid: "mmMoPLrw/bzrH2R5sZfzqI3Q2Ak=".try_into().unwrap(),
+            ver: 1,
+        }
+    }
+
+    #[test]
+    fn rfc9068_claims() {
+        let claims = serde_json::to_value(Rfc9068AccessToken::from(access_token())).unwrap();
+        assert_eq!(claims["client_id"], json!("0jrabyQWm4B9zVJPbotY"));
+        assert_eq!(claims["scope"], json!("openid"));
+        assert_eq!(claims.get("cid"), None::<&Value>);
+        assert_eq!(claims.get("scp"), None::<&Value>);
+    }
+
+    #[test]
+    fn decode_either_profile() {
+        let okta = serde_json::to_value(access_token()).unwrap();
+        let rfc9068 = serde_json::to_value(Rfc9068AccessToken::from(access_token())).unwrap();
+        for claims in [okta, rfc9068] {
+            let token =
+                AccessToken::from(serde_json::from_value::<ProfiledAccessToken>(claims).unwrap());
+            assert_eq!(token.cid.to_string(), "0jrabyQWm4B9zVJPbotY");
+            assert_eq!(token.scp, [Scope::Openid].into());
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::bounded_string::NonEmptyString;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use serde_with::formats::SpaceSeparator;
+use serde_with::{serde_as, StringWithSeparator};
+use std::collections::HashMap;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc9396#section-2
+#[derive(Clone, Deserialize, Serialize)]
+pub struct AuthorizationDetail {
+    pub r#type: NonEmptyString,
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+// The schema of an authorization details type: the fields it requires
+#[serde_as]
+#[derive(Clone, Deserialize)]
+pub struct AuthorizationDetailsType {
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, String>")]
+    #[serde(default)]
+    pub required: Vec<String>,
+    pub r#type: NonEmptyString,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc9396#section-5
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown authorization details type `{0}`")]
+    UnknownType(NonEmptyString),
+    #[error("authorization details of type `{0}` miss the field `{1}`")]
+    MissingField(NonEmptyString, String),
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        "invalid_authorization_details"
+    }
+}
+
+pub fn validate(
+    details: &[AuthorizationDetail],
+    types: &HashMap<NonEmptyString, AuthorizationDetailsType>,
+) -> Result<(), Error> {
+    for detail in details {
+        let schema = types
+            .get(&detail.r#type)
+            .ok_or_else(|| Error::UnknownType(detail.r#type.clone()))?;
+        if let Some(field) = schema
+            .required
+            .iter()
+            .find(|field| !detail.fields.contains_key(*field))
+        {
+            return Err(Error::MissingField(detail.r#type.clone(), field.clone()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn types() -> HashMap<NonEmptyString, AuthorizationDetailsType> {
+        let schema = AuthorizationDetailsType {
+            required: vec!["instructedAmount".into()],
+            r#type: "payment_initiation".try_into().unwrap(),
+        };
+        [(schema.r#type.clone(), schema)].into()
+    }
+
+    fn detail(value: Value) -> AuthorizationDetail {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn known_type_with_required_fields() {
+        let details = [detail(json!({
+            "type": "payment_initiation",
+            "instructedAmount": {"currency": "EUR", "amount": "123.50"},
+        }))];
+        assert!(validate(&details, &types()).is_ok());
+    }
+
+    #[test]
+    fn unknown_type() {
+        let details = [detail(json!({"type": "account_information"}))];
+        assert!(matches!(
+            validate(&details, &types()),
+            Err(Error::UnknownType(_))
+        ));
+    }
+
+    #[test]
+    fn missing_required_field() {
+        let details = [detail(json!({"type": "payment_initiation"}))];
+        assert!(matches!(
+            validate(&details, &types()),
+            Err(Error::MissingField(_, _))
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::bounded_string::NonEmptyString;
+use serde::Deserialize;
+
+// https://openid.net/specs/openid-connect-core-1_0.html#ClaimsParameter
+#[derive(Clone, Default, Deserialize)]
+pub struct ClaimsRequest {
+    #[serde(default)]
+    pub id_token: IdTokenClaims,
+}
+
+#[derive(Clone, Default, Deserialize)]
+pub struct IdTokenClaims {
+    #[serde(default)]
+    pub acr: Option<ClaimRequest>,
+}
+
+// https://openid.net/specs/openid-connect-core-1_0.html#IndividualClaimsRequests
+#[derive(Clone, Default, Deserialize)]
+pub struct ClaimRequest {
+    #[serde(default)]
+    pub essential: bool,
+    #[serde(default)]
+    pub value: Option<NonEmptyString>,
+    #[serde(default)]
+    pub values: Vec<NonEmptyString>,
+}
+
+// https://openid.net/specs/openid-connect-core-1_0.html#acrSemantics
+pub struct AcrRequest {
+    pub essential: bool,
+    pub values: Vec<NonEmptyString>,
+}
+
+impl AcrRequest {
+    pub fn accepts(&self, acr: &NonEmptyString) -> bool {
+        self.values.is_empty() || self.values.iter().any(|value| satisfies(acr, value))
+    }
+}
+
+// Numeric `acr` values are levels where a higher level satisfies a lower one,
+// any other value is only satisfied by itself
+fn satisfies(acr: &NonEmptyString, requested: &NonEmptyString) -> bool {
+    match (
+        acr.to_string().parse::<u32>(),
+        requested.to_string().parse::<u32>(),
+    ) {
+        (Ok(level), Ok(requested)) => level >= requested,
+        _ => acr == requested,
+    }
+}
+
+impl From<ClaimRequest> for AcrRequest {
+    fn from(claim: ClaimRequest) -> Self {
+        let mut values = claim.values;
+        values.extend(claim.value);
+        Self {
+            essential: claim.essential,
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn essential_acr() {
+        let claims: ClaimsRequest =
+            serde_json::from_str(r#"{"id_token":{"acr":{"essential":true,"values":["2"]}}}"#)
+                .unwrap();
+        let request = AcrRequest::from(claims.id_token.acr.unwrap());
+        let low = "1".try_into().unwrap();
+        let high = "2".try_into().unwrap();
+        assert!(request.essential && !request.accepts(&low) && request.accepts(&high))
+    }
+
+    #[test]
+    fn acr_levels() {
+        let request = AcrRequest {
+            essential: false,
+            values: vec!["2".try_into().unwrap(), "3".try_into().unwrap()],
+        };
+        assert!(!request.accepts(&"1".try_into().unwrap()));
+        assert!(request.accepts(&"2".try_into().unwrap()));
+        assert!(request.accepts(&"4".try_into().unwrap()));
+        assert!(!request.accepts(&"urn:mace:incommon:iap:silver".try_into().unwrap()));
+    }
+
+    #[test]
+    fn named_acr() {
+        let silver: NonEmptyString = "urn:mace:incommon:iap:silver".try_into().unwrap();
+        let request = AcrRequest {
+            essential: false,
+            values: vec![silver.clone()],
+        };
+        assert!(request.accepts(&silver) && !request.accepts(&"2".try_into().unwrap()))
+    }
+
+    #[test]
+    fn any_acr() {
+        let request = AcrRequest {
+            essential: false,
+            values: Vec::new(),
+        };
+        assert!(request.accepts(&"1".try_into().unwrap()))
+    }
+}
@@ -1,5 +1,28 @@
 use super::time::UnixTime;
 use super::AuthenticationMethod;
-use crate::bounded_string::SecureString;
+use crate::bounded_string::{NonEmptyString, SecureString};
 use axum::http::Uri;
 use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct IdToken {
+    pub acr: NonEmptyString,
+    pub amr: Vec<AuthenticationMethod>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_hash: Option<String>,
+    pub aud: SecureString,
+    pub auth_time: UnixTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub c_hash: Option<String>,
+    pub exp: UnixTime,
+    pub iat: UnixTime,
+    #[serde(with = "http_serde::uri")]
+    pub iss: Uri,
+    pub jti: SecureString,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<NonEmptyString>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sid: Option<SecureString>,
+    pub sub: SecureString,
+    pub ver: u32,
+}
@@ -1,18 +1,21 @@
 pub mod access_token;
+pub mod authorization_details;
+pub mod claims;
+pub mod id_token;
 pub mod pkce;
+pub mod resource;
 pub mod time;
 
-use crate::bounded_string::SecureString;
-use axum::http::Uri;
-use derive_more::derive::{AsRef, Display, FromStr};
+use derive_more::derive::{AsRef, Display, From, FromStr};
 use serde::{Deserialize, Serialize};
 use serde_with::base64::{Base64, Standard};
 use serde_with::formats::Padded;
 use serde_with::serde_as;
-use time::UnixTime;
+use std::collections::HashSet;
+use thiserror::Error;
 
 #[serde_as]
-#[derive(AsRef, Clone, Deserialize)]
+#[derive(AsRef, Clone, Deserialize, From)]
 pub struct Secret(#[serde_as(as = "Base64<Standard, Padded>")] Vec<u8>);
 
 #[derive(Clone, Deserialize)]
@@ -20,28 +23,192 @@ pub enum CodeChallengeMethod {
     S256,
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ResponseMode {
     FormPost,
+    Fragment,
+    Query,
+    // https://openid.net/specs/oauth-v2-jarm.html#section-2.3
+    Jwt,
+    #[serde(rename = "form_post.jwt")]
+    FormPostJwt,
+    #[serde(rename = "fragment.jwt")]
+    FragmentJwt,
+    #[serde(rename = "query.jwt")]
+    QueryJwt,
 }
 
-#[derive(Clone, Deserialize)]
+impl ResponseMode {
+    pub fn is_jwt(&self) -> bool {
+        matches!(
+            self,
+            Self::Jwt | Self::FormPostJwt | Self::FragmentJwt | Self::QueryJwt
+        )
+    }
+
+    // The mode used to deliver the (possibly JWT secured) response parameters
+    pub fn delivery(&self, response_type: &ResponseType) -> Delivery {
+        match self {
+            Self::FormPost | Self::FormPostJwt => Delivery::FormPost,
+            Self::Fragment | Self::FragmentJwt => Delivery::Fragment,
+            Self::Query | Self::QueryJwt => Delivery::Query,
+            Self::Jwt if *response_type == ResponseType::Code => Delivery::Query,
+            Self::Jwt => Delivery::Fragment,
+        }
+    }
+}
+
+// The decision taken by the user on an approval page
+#[derive(Deserialize)]
 #[serde(rename_all = "snake_case")]
+pub enum Action {
+    Approve,
+    Deny,
+}
+
+// How the response parameters reach the redirection URI
+#[derive(Clone, Copy, PartialEq)]
+pub enum Delivery {
+    FormPost,
+    Fragment,
+    Query,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "String")]
 pub enum ResponseType {
     Code,
+    IdToken,
+    IdTokenToken,
+    CodeIdToken,
+    CodeToken,
+    CodeIdTokenToken,
 }
 
-#[derive(Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub enum GrantType {
-    AuthorizationCode,
+#[derive(Debug, Error)]
+#[error("unsupported response type `{0}`")]
+pub struct ResponseTypeError(String);
+
+impl ResponseType {
+    pub fn code(&self) -> bool {
+        matches!(
+            self,
+            Self::Code | Self::CodeIdToken | Self::CodeToken | Self::CodeIdTokenToken
+        )
+    }
+
+    pub fn id_token(&self) -> bool {
+        matches!(
+            self,
+            Self::IdToken | Self::IdTokenToken | Self::CodeIdToken | Self::CodeIdTokenToken
+        )
+    }
+
+    pub fn token(&self) -> bool {
+        matches!(
+            self,
+            Self::IdTokenToken | Self::CodeToken | Self::CodeIdTokenToken
+        )
+    }
+}
+
+impl TryFrom<&str> for ResponseType {
+    type Error = ResponseTypeError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let values: HashSet<&str> = value.split_whitespace().collect();
+        let has = |v| values.contains(v);
+        let response_type = match (values.len(), has("code"), has("id_token"), has("token")) {
+            (1, true, false, false) => Self::Code,
+            (1, false, true, false) => Self::IdToken,
+            (2, false, true, true) => Self::IdTokenToken,
+            (2, true, true, false) => Self::CodeIdToken,
+            (2, true, false, true) => Self::CodeToken,
+            (3, true, true, true) => Self::CodeIdTokenToken,
+            _ => return Err(ResponseTypeError(value.into())),
+        };
+        Ok(response_type)
+    }
+}
+
+impl TryFrom<String> for ResponseType {
+    type Error = ResponseTypeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_ref())
+    }
+}
+
+// https://openid.net/specs/openid-connect-core-1_0.html#AuthRequest
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Prompt {
+    None,
+    Login,
+    Consent,
+    SelectAccount,
+}
+
+#[derive(Debug, Error)]
+#[error("unsupported prompt `{0}`")]
+pub struct PromptError(String);
+
+impl std::str::FromStr for Prompt {
+    type Err = PromptError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "none" => Ok(Self::None),
+            "login" => Ok(Self::Login),
+            "consent" => Ok(Self::Consent),
+            "select_account" => Ok(Self::SelectAccount),
+            _ => Err(PromptError(value.into())),
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+#[derive(Serialize)]
+pub struct ErrorResponse {
+    error: &'static str,
+    error_description: String,
+}
+
+impl ErrorResponse {
+    pub fn new(error: &'static str, description: impl ToString) -> Self {
+        Self {
+            error,
+            error_description: description.to_string(),
+        }
+    }
+}
+
+// A parameter value of a `WWW-Authenticate` challenge
+// https://datatracker.ietf.org/doc/html/rfc9110#section-5.6.4
+pub fn quoted_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            ' '..='~' => quoted.push(c),
+            _ => quoted.push('?'),
+        }
+    }
+    quoted.push('"');
+    quoted
 }
 
 #[derive(Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TokenType {
     Bearer,
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-5
+    #[serde(rename = "DPoP")]
+    Dpop,
 }
 
 #[derive(Clone, Debug, Deserialize, Display, FromStr, Hash, PartialEq, Eq, Serialize)]
@@ -55,7 +222,46 @@ pub enum Scope {
     Profile,
 }
 
-#[derive(Display, Serialize)]
+impl Scope {
+    pub fn all() -> HashSet<Self> {
+        [
+            Self::Address,
+            Self::Email,
+            Self::Groups,
+            Self::Openid,
+            Self::Phone,
+            Self::Profile,
+        ]
+        .into()
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#ScopeClaims
+    pub fn claims(&self) -> &'static [&'static str] {
+        match self {
+            Self::Address => &["address"],
+            Self::Email => &["email", "email_verified"],
+            Self::Groups => &["groups"],
+            Self::Openid => &["sub"],
+            Self::Phone => &["phone_number"],
+            Self::Profile => &[
+                "name",
+                "family_name",
+                "given_name",
+                "middle_name",
+                "nickname",
+                "preferred_username",
+                "profile",
+                "zoneinfo",
+                "locale",
+                "updated_at",
+            ],
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8176#section-2
+#[derive(Clone, Debug, Deserialize, Display, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 #[allow(unused)]
 pub enum AuthenticationMethod {
     #[display("Password authentication")]
@@ -82,17 +288,38 @@ pub enum AuthenticationMethod {
     Mfa,
 }
 
-#[derive(Serialize)]
-pub struct IdToken {
-    pub amr: Vec<AuthenticationMethod>,
-    pub at_hash: String,
-    pub aud: SecureString,
-    pub auth_time: UnixTime,
-    pub exp: UnixTime,
-    pub iat: UnixTime,
-    #[serde(with = "http_serde::uri")]
-    pub iss: Uri,
-    pub jti: SecureString,
-    pub sub: SecureString,
-    pub ver: u32,
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn response_type_order_insensitive() {
+        let result = ResponseType::try_from("token id_token code");
+        assert_eq!(result.unwrap(), ResponseType::CodeIdTokenToken)
+    }
+
+    #[test]
+    fn response_type_hybrid() {
+        let result = ResponseType::try_from("code id_token").unwrap();
+        assert!(result.code() && result.id_token() && !result.token())
+    }
+
+    #[test]
+    fn jwt_response_mode_delivery() {
+        let code = ResponseMode::Jwt.delivery(&ResponseType::Code);
+        let hybrid = ResponseMode::Jwt.delivery(&ResponseType::CodeIdToken);
+        assert!(code == Delivery::Query && hybrid == Delivery::Fragment)
+    }
+
+    #[test]
+    fn quoted_string_escaped() {
+        let quoted = quoted_string(r#"a "b" \ é"#);
+        assert_eq!(quoted, r#""a \"b\" \\ ?""#)
+    }
+
+    #[test]
+    fn response_type_token_only() {
+        let result = ResponseType::try_from("token");
+        assert!(result.is_err())
+    }
 }
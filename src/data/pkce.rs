@@ -16,7 +16,7 @@ pub enum ParseError {
 
 #[derive(AsRef, Clone, Debug, Deserialize, Display)]
 #[as_ref(forward)]
-#[serde(try_from = "&str")]
+#[serde(try_from = "String")]
 struct PkceCode(BoundedString<40, 128>);
 
 impl TryFrom<&str> for PkceCode {
@@ -0,0 +1,69 @@
+use axum::async_trait;
+use axum::body::{Body, Bytes};
+use axum::extract::rejection::FormRejection;
+use axum::extract::{Form, FromRequest, Request};
+use serde::de::DeserializeOwned;
+
+// https://datatracker.ietf.org/doc/html/rfc8707#section-2
+pub const RESOURCE: &str = "resource";
+
+// The `resource` parameter can be repeated, which the url encoded extractors
+// cannot represent: its values are joined with spaces, which cannot appear in
+// an absolute URI
+pub fn join_resources(encoded: &[u8]) -> Option<String> {
+    let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(encoded).ok()?;
+    let (resources, mut pairs): (Vec<_>, Vec<_>) =
+        pairs.into_iter().partition(|(key, _)| key == RESOURCE);
+    if resources.len() < 2 {
+        return None;
+    }
+    let resources = resources.into_iter().map(|(_, value)| value);
+    pairs.push((RESOURCE.into(), resources.collect::<Vec<_>>().join(" ")));
+    serde_urlencoded::to_string(pairs).ok()
+}
+
+// A form extractor accepting repeated `resource` parameters
+pub struct ResourceForm<T>(pub T);
+
+#[async_trait]
+impl<S: Send + Sync, T: DeserializeOwned> FromRequest<S> for ResourceForm<T> {
+    type Rejection = FormRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let (parts, body) = req.into_parts();
+        let body = Bytes::from_request(Request::from_parts(parts.clone(), body), state).await?;
+        let body = match join_resources(&body) {
+            Some(joined) => Body::from(joined),
+            None => Body::from(body),
+        };
+        let Form(params) = Form::from_request(Request::from_parts(parts, body), state).await?;
+        Ok(Self(params))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn single_resource_is_untouched() {
+        assert_eq!(
+            join_resources(b"resource=https%3A%2F%2Fa&scope=openid"),
+            None
+        );
+    }
+
+    #[test]
+    fn repeated_resources_are_joined() {
+        let joined =
+            join_resources(b"resource=https%3A%2F%2Fa&scope=openid&resource=https%3A%2F%2Fb");
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(&joined.unwrap()).unwrap();
+        assert_eq!(
+            pairs,
+            vec![
+                ("scope".into(), "openid".into()),
+                ("resource".into(), "https://a https://b".into())
+            ]
+        );
+    }
+}
@@ -21,6 +21,12 @@ impl UnixTime {
     }
 }
 
+impl From<UnixTime> for u64 {
+    fn from(time: UnixTime) -> Self {
+        time.0
+    }
+}
+
 impl Add<u64> for UnixTime {
     type Output = Self;
 
@@ -0,0 +1,59 @@
+use crate::authorize::extractor::scope;
+use crate::bounded_string::SecureString;
+use crate::data::{Action, Scope};
+use crate::state::device::UserCode;
+use axum::extract::{Form, FromRequest, FromRequestParts, Query};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashSet;
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct DeviceAuthorizationParams {
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(default, deserialize_with = "client_secret")]
+    pub client_secret: Option<SecureString>,
+    #[serde(deserialize_with = "scope")]
+    pub scope: HashSet<Scope>,
+}
+
+#[derive(Deserialize, FromRequestParts)]
+#[from_request(via(Query))]
+pub struct VerificationQuery {
+    #[serde(default)]
+    pub user_code: String,
+}
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct VerificationParams {
+    #[serde(deserialize_with = "action")]
+    pub action: Action,
+    #[serde(deserialize_with = "user_code")]
+    pub user_code: UserCode,
+}
+
+fn client_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn client_secret<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecureString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_secret`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn action<'de, D: Deserializer<'de>>(d: D) -> Result<Action, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `action`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn user_code<'de, D: Deserializer<'de>>(d: D) -> Result<UserCode, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `user_code`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
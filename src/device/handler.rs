@@ -0,0 +1,237 @@
+use super::extractor::{DeviceAuthorizationParams, VerificationParams, VerificationQuery};
+use crate::bounded_string::SecureString;
+use crate::data::time::UnixTime;
+use crate::data::{Action, ErrorResponse};
+use crate::mtls::ClientCertificate;
+use crate::state::client::GrantType;
+use crate::state::device::DeviceSession;
+use crate::state::{AppState, Grant};
+use crate::token::handler::{authenticate_device_client, InvalidParamError};
+use askama::Template;
+use askama_axum::IntoResponse;
+use axum::http::StatusCode;
+use axum::response::{Response, Result};
+use axum::{Extension, Json};
+use serde::Serialize;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc8628#section-3.2
+#[derive(Serialize)]
+pub struct DeviceAuthorizationResponse {
+    device_code: SecureString,
+    expires_in: u64,
+    interval: u64,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: String,
+}
+
+#[derive(Template)]
+#[template(path = "device.html")]
+pub struct VerificationPage {
+    user_code: String,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidClient(InvalidParamError),
+    #[error("client `{0}` is not registered for the `{1}` grant type")]
+    UnauthorizedClient(SecureString, GrantType),
+}
+
+impl Error {
+    // https://datatracker.ietf.org/doc/html/rfc8628#section-3.2
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidClient(_) => "invalid_client",
+            Self::UnauthorizedClient(..) => "unauthorized_client",
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Self::InvalidClient(_) => StatusCode::UNAUTHORIZED,
+            Self::UnauthorizedClient(..) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(ErrorResponse::new(self.code(), &self))).into_response()
+    }
+}
+
+pub async fn device_authorization(
+    state: AppState,
+    certificate: Option<Extension<ClientCertificate>>,
+    params: DeviceAuthorizationParams,
+) -> Result<Json<DeviceAuthorizationResponse>> {
+    let certificate = certificate
+        .as_ref()
+        .map(|Extension(certificate)| certificate);
+    let client =
+        authenticate_device_client(&state, &params.client_id, params.client_secret, certificate)
+            .map_err(Error::InvalidClient)?;
+    if !client.allows_grant_type(GrantType::DeviceCode) {
+        return Err(Error::UnauthorizedClient(client.client_id, GrantType::DeviceCode).into());
+    }
+    let grant = Grant {
+        auth_time: UnixTime::now(),
+        authentication: state.authentication(),
+        authorization_details: Vec::new(),
+        client_id: client.client_id,
+        nonce: None,
+        resource: Vec::new(),
+        scope: params.scope,
+        sid: None,
+        user_id: state.get_user().id(),
+    };
+    let device_code = state.gen_secure_string();
+    let user_code = state.gen_user_code();
+    let session = DeviceSession::new(
+        grant,
+        user_code.clone(),
+        state.device_expiration(),
+        state.device_interval(),
+    );
+    state.set_device_session(device_code.clone(), session);
+    let verification_uri = state.endpoint("/device");
+    Ok(Json(DeviceAuthorizationResponse {
+        device_code,
+        expires_in: state.device_expiration(),
+        interval: state.device_interval(),
+        verification_uri_complete: format!("{verification_uri}?user_code={user_code}"),
+        user_code: user_code.to_string(),
+        verification_uri,
+    }))
+}
+
+pub async fn verification_page(query: VerificationQuery) -> impl IntoResponse {
+    VerificationPage {
+        user_code: query.user_code,
+    }
+}
+
+pub async fn verify(state: AppState, params: VerificationParams) -> impl IntoResponse {
+    let approved = matches!(params.action, Action::Approve);
+    if state.resolve_device_session(&params.user_code, approved) {
+        let outcome = if approved { "approved" } else { "denied" };
+        let message = format!("device {outcome}, you can return to your device");
+        (StatusCode::OK, message)
+    } else {
+        let user_code = params.user_code;
+        let message = format!("no pending device session associated with user code `{user_code}`");
+        (StatusCode::BAD_REQUEST, message)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET};
+    use axum::http::StatusCode;
+
+    const OTHER_CLIENT_ID: &str = "otherClientIdentifier";
+
+    async fn device_authorization(
+        state: &crate::state::AppState,
+        params: &[(&str, &str)],
+    ) -> testing::TestResponse {
+        let defaults = [
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("scope", "openid"),
+        ];
+        let form = testing::merge(&defaults, params);
+        testing::post_form(state, "/device_authorization", &form).await
+    }
+
+    async fn approved_device_code(state: &crate::state::AppState) -> String {
+        let response = device_authorization(state, &[]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let response = response.json();
+        assert_eq!(
+            response["verification_uri"],
+            "https://login.helloprima.com/device"
+        );
+        let user_code = response["user_code"].as_str().unwrap();
+        let params = [("user_code", user_code), ("action", "approve")];
+        let verified = testing::post_form(state, "/device", &params).await;
+        assert_eq!(verified.status, StatusCode::OK);
+        response["device_code"].as_str().unwrap().to_string()
+    }
+
+    async fn redeem(
+        state: &crate::state::AppState,
+        client_id: &str,
+        device_code: &str,
+    ) -> testing::TestResponse {
+        let params = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("client_id", client_id),
+            ("client_secret", CLIENT_SECRET),
+            ("device_code", device_code),
+        ];
+        testing::post_form(state, "/token", &params).await
+    }
+
+    #[tokio::test]
+    async fn device_code_redeemed_by_its_client() {
+        let state = testing::state(&[]);
+        let device_code = approved_device_code(&state).await;
+        let response = redeem(&state, CLIENT_ID, &device_code).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.json()["access_token"].is_string());
+    }
+
+    #[tokio::test]
+    async fn device_code_bound_to_its_client() {
+        let state = testing::state(&[]);
+        let device_code = approved_device_code(&state).await;
+        let response = redeem(&state, OTHER_CLIENT_ID, &device_code).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_grant");
+        // The rejected attempt does not consume the code of the client
+        let response = redeem(&state, CLIENT_ID, &device_code).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    #[tokio::test]
+    async fn confidential_client_authenticated_at_token() {
+        let state = testing::state(&[]);
+        let device_code = approved_device_code(&state).await;
+        let params = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ("client_id", CLIENT_ID),
+            ("device_code", &device_code),
+        ];
+        let response = testing::post_form(&state, "/token", &params).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json()["error"], "invalid_client");
+    }
+
+    #[tokio::test]
+    async fn confidential_client_authenticated_at_device_authorization() {
+        let state = testing::state(&[]);
+        let params = [("client_id", CLIENT_ID), ("scope", "openid")];
+        let response = testing::post_form(&state, "/device_authorization", &params).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json()["error"], "invalid_client");
+        let response = device_authorization(
+            &state,
+            &[("client_secret", "0000000000000000000000000000000000000000")],
+        )
+        .await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json()["error"], "invalid_client");
+    }
+
+    #[tokio::test]
+    async fn device_grant_allowed_to_the_client() {
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__GRANT_TYPES", "authorization_code"),
+        ]);
+        let response = device_authorization(&state, &[]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "unauthorized_client");
+    }
+}
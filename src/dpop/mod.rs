@@ -0,0 +1,461 @@
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::crypto::{hash, jwk_thumbprint};
+use crate::data::time::UnixTime;
+use crate::data::{quoted_string, ErrorResponse};
+use crate::state::AppState;
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::header::{AUTHORIZATION, HOST, WWW_AUTHENTICATE};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, HeaderValue, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::Value;
+use std::convert::Infallible;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc9449#section-4.1
+pub const DPOP: HeaderName = HeaderName::from_static("dpop");
+// https://datatracker.ietf.org/doc/html/rfc9449#section-8
+pub const DPOP_NONCE: HeaderName = HeaderName::from_static("dpop-nonce");
+
+// How far the `iat` of a proof may be from the current time, in seconds
+pub const PROOF_LEEWAY: u64 = 60;
+
+const ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+];
+
+// The members of the private keys of every key type
+// https://datatracker.ietf.org/doc/html/rfc7518#section-6
+const PRIVATE_MEMBERS: [&str; 8] = ["d", "dp", "dq", "k", "oth", "p", "q", "qi"];
+
+// https://datatracker.ietf.org/doc/html/rfc9449#section-4.2
+#[derive(Deserialize)]
+struct ProofClaims {
+    #[serde(default)]
+    ath: Option<String>,
+    htm: String,
+    #[serde(with = "http_serde::uri")]
+    htu: Uri,
+    iat: UnixTime,
+    jti: NonEmptyString,
+    #[serde(default)]
+    nonce: Option<SecureString>,
+}
+
+// The DPoP proof of a request along with the method and target it is bound to
+pub struct DpopRequest {
+    host: Option<String>,
+    method: Method,
+    path: String,
+    proof: Option<String>,
+}
+
+// The access token of the `Authorization` header, with the scheme it was sent with
+pub enum TokenAuthorization {
+    Bearer(String),
+    Dpop(String),
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("missing DPoP proof")]
+    MissingProof,
+    #[error("invalid DPoP proof: {0}")]
+    InvalidProof(#[from] jsonwebtoken::errors::Error),
+    #[error("DPoP proof must have `typ` `dpop+jwt`, an asymmetric `alg` and a `jwk`")]
+    InvalidHeader,
+    #[error("DPoP proof `jwk` must not contain a private key")]
+    PrivateKey,
+    #[error("DPoP proof `{0}` claim does not match the request")]
+    Mismatch(&'static str),
+    #[error("DPoP proof `iat` is more than {PROOF_LEEWAY} seconds away from now")]
+    IssuedAt,
+    #[error("DPoP proof `jti` `{0}` was already used")]
+    Replay(NonEmptyString),
+    #[error("the server requires a DPoP nonce")]
+    UseNonce(SecureString),
+    #[error("access token is bound to another DPoP key")]
+    KeyMismatch,
+    #[error("access token is not bound to a DPoP key")]
+    Unbound,
+    #[error("DPoP bound access token sent with the `Bearer` scheme")]
+    BearerScheme,
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UseNonce(_) => "use_dpop_nonce",
+            Self::KeyMismatch | Self::Unbound | Self::BearerScheme => "invalid_token",
+            _ => "invalid_dpop_proof",
+        }
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-8
+    fn with_nonce(&self, mut response: Response) -> Response {
+        if let Self::UseNonce(nonce) = self {
+            let nonce = HeaderValue::from_str(nonce.as_ref()).unwrap();
+            response.headers_mut().insert(DPOP_NONCE, nonce);
+        }
+        response
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-5
+    pub fn token_response(&self) -> Response {
+        let response = ErrorResponse::new(self.code(), self);
+        self.with_nonce((StatusCode::BAD_REQUEST, Json(response)).into_response())
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-7.1
+    pub fn resource_response(&self) -> Response {
+        let algs = ALGORITHMS.map(|alg| format!("{alg:?}")).join(" ");
+        let challenge = format!(
+            r#"DPoP error="{}", error_description={}, algs="{algs}""#,
+            self.code(),
+            quoted_string(&self.to_string())
+        );
+        let header = [(WWW_AUTHENTICATE, challenge)];
+        self.with_nonce((StatusCode::UNAUTHORIZED, header, self.to_string()).into_response())
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for DpopRequest {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name| {
+            let value = parts.headers.get(name)?.to_str().ok()?;
+            Some(value.to_string())
+        };
+        Ok(Self {
+            host: header(HOST),
+            method: parts.method.clone(),
+            path: parts.uri.path().to_string(),
+            proof: header(DPOP),
+        })
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for TokenAuthorization {
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let value = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let (scheme, token) = value.split_once(' ').unwrap_or_default();
+        match scheme.to_lowercase().as_str() {
+            "bearer" => Ok(Self::Bearer(token.trim().to_string())),
+            "dpop" => Ok(Self::Dpop(token.trim().to_string())),
+            _ => Err((
+                StatusCode::UNAUTHORIZED,
+                "missing `Bearer` or `DPoP` authorization",
+            )),
+        }
+    }
+}
+
+impl TokenAuthorization {
+    pub fn token(&self) -> &str {
+        match self {
+            Self::Bearer(token) | Self::Dpop(token) => token,
+        }
+    }
+}
+
+impl DpopRequest {
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-4.3
+    // Returns the thumbprint of the key that signed the proof, if any
+    pub fn verify(
+        &self,
+        state: &AppState,
+        access_token: Option<&str>,
+    ) -> Result<Option<String>, Error> {
+        let Some(proof) = &self.proof else {
+            return Ok(None);
+        };
+        let header = decode_header(proof)?;
+        let jwk = match (header.typ.as_deref(), header.jwk) {
+            (Some("dpop+jwt"), Some(jwk)) if ALGORITHMS.contains(&header.alg) => jwk,
+            _ => return Err(Error::InvalidHeader),
+        };
+        if has_private_key(proof) {
+            return Err(Error::PrivateKey);
+        }
+        let mut validation = Validation::new(header.alg);
+        validation.required_spec_claims.clear();
+        validation.validate_aud = false;
+        validation.validate_exp = false;
+        let key = DecodingKey::from_jwk(&jwk)?;
+        let claims: ProofClaims = decode(proof, &key, &validation)?.claims;
+        if claims.htm != self.method.as_str() {
+            return Err(Error::Mismatch("htm"));
+        }
+        // The scheme is not compared since the server may sit behind a TLS proxy
+        let authority = claims.htu.authority().map(|authority| authority.as_str());
+        if claims.htu.path() != self.path
+            || self
+                .host
+                .as_deref()
+                .is_some_and(|host| Some(host) != authority)
+        {
+            return Err(Error::Mismatch("htu"));
+        }
+        if u64::from(UnixTime::now()).abs_diff(claims.iat.into()) > PROOF_LEEWAY {
+            return Err(Error::IssuedAt);
+        }
+        if let Some(access_token) = access_token {
+            if claims.ath != Some(hash(access_token)) {
+                return Err(Error::Mismatch("ath"));
+            }
+        }
+        if state.dpop_nonce_required()
+            && !claims
+                .nonce
+                .is_some_and(|nonce| state.is_dpop_nonce(&nonce))
+        {
+            return Err(Error::UseNonce(state.dpop_nonce()));
+        }
+        if !state.use_dpop_jti(claims.jti.clone(), claims.iat + PROOF_LEEWAY) {
+            return Err(Error::Replay(claims.jti));
+        }
+        Ok(Some(jwk_thumbprint(&jwk)))
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-7
+    pub fn verify_binding(
+        &self,
+        state: &AppState,
+        authorization: &TokenAuthorization,
+        jkt: Option<&str>,
+    ) -> Result<(), Error> {
+        match (authorization, jkt) {
+            (TokenAuthorization::Bearer(_), None) => Ok(()),
+            (TokenAuthorization::Bearer(_), Some(_)) => Err(Error::BearerScheme),
+            (TokenAuthorization::Dpop(_), None) => Err(Error::Unbound),
+            (TokenAuthorization::Dpop(token), Some(jkt)) => {
+                let proven = self.verify(state, Some(token))?;
+                match proven {
+                    Some(proven) if proven == jkt => Ok(()),
+                    Some(_) => Err(Error::KeyMismatch),
+                    None => Err(Error::MissingProof),
+                }
+            }
+        }
+    }
+}
+
+// The `jwk` parsed by `decode_header` silently drops the private members
+fn has_private_key(proof: &str) -> bool {
+    let header = proof.split('.').next().unwrap_or_default();
+    let Ok(header) = URL_SAFE_NO_PAD.decode(header) else {
+        return false;
+    };
+    let Ok(header) = serde_json::from_slice::<Value>(&header) else {
+        return false;
+    };
+    PRIVATE_MEMBERS
+        .iter()
+        .any(|member| header["jwk"].get(member).is_some())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+    use jsonwebtoken::crypto::sign;
+    use jsonwebtoken::EncodingKey;
+    use p256::elliptic_curve::sec1::ToEncodedPoint;
+    use p256::pkcs8::EncodePrivateKey;
+    use p256::SecretKey;
+    use serde_json::json;
+
+    const ACCESS_TOKEN: &str = "accessTokenOfTheRequest";
+
+    fn request(proof: String) -> DpopRequest {
+        DpopRequest {
+            host: Some("localhost:4000".to_string()),
+            method: Method::POST,
+            path: "/token".to_string(),
+            proof: Some(proof),
+        }
+    }
+
+    fn jwk(key: &SecretKey) -> Value {
+        let point = key.public_key().to_encoded_point(false);
+        json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+            "y": URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+        })
+    }
+
+    // The header is built by hand so that it may carry a private key
+    fn sign_proof(key: &SecretKey, jwk: Value, overrides: Value) -> String {
+        let header = json!({ "alg": "ES256", "typ": "dpop+jwt", "jwk": jwk });
+        let mut claims = json!({
+            "htm": "POST",
+            "htu": "https://localhost:4000/token",
+            "iat": u64::from(UnixTime::now()),
+            "jti": URL_SAFE_NO_PAD.encode(rand::random::<[u8; 16]>()),
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(overrides.as_object().unwrap().clone());
+        let message = [header, claims]
+            .map(|part| URL_SAFE_NO_PAD.encode(part.to_string()))
+            .join(".");
+        let der = key.to_pkcs8_der().unwrap();
+        let key = EncodingKey::from_ec_der(der.as_bytes());
+        let signature = sign(message.as_bytes(), &key, Algorithm::ES256).unwrap();
+        format!("{message}.{signature}")
+    }
+
+    fn proof(overrides: Value) -> String {
+        let key = SecretKey::random(&mut rand::thread_rng());
+        sign_proof(&key, jwk(&key), overrides)
+    }
+
+    #[test]
+    fn valid_proof() {
+        let state = testing::state(&[]);
+        let key = SecretKey::random(&mut rand::thread_rng());
+        let proof = sign_proof(&key, jwk(&key), json!({}));
+        let thumbprint = request(proof).verify(&state, None).unwrap();
+        assert_eq!(
+            thumbprint,
+            Some(jwk_thumbprint(&serde_json::from_value(jwk(&key)).unwrap()))
+        );
+    }
+
+    #[test]
+    fn without_proof() {
+        let state = testing::state(&[]);
+        let request = DpopRequest {
+            proof: None,
+            ..request(String::new())
+        };
+        assert!(request.verify(&state, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn htm_mismatch() {
+        let state = testing::state(&[]);
+        let request = request(proof(json!({ "htm": "GET" })));
+        let result = request.verify(&state, None);
+        assert!(matches!(result, Err(Error::Mismatch("htm"))));
+    }
+
+    #[test]
+    fn htu_mismatch() {
+        let state = testing::state(&[]);
+        for htu in [
+            "https://localhost:4000/userinfo",
+            "https://example.com/token",
+        ] {
+            let request = request(proof(json!({ "htu": htu })));
+            let result = request.verify(&state, None);
+            assert!(matches!(result, Err(Error::Mismatch("htu"))));
+        }
+    }
+
+    #[test]
+    fn iat_out_of_leeway() {
+        let state = testing::state(&[]);
+        let now = u64::from(UnixTime::now());
+        for iat in [now - 2 * PROOF_LEEWAY, now + 2 * PROOF_LEEWAY] {
+            let request = request(proof(json!({ "iat": iat })));
+            let result = request.verify(&state, None);
+            assert!(matches!(result, Err(Error::IssuedAt)));
+        }
+    }
+
+    #[test]
+    fn ath_of_the_access_token() {
+        let state = testing::state(&[]);
+        let request = request(proof(json!({ "ath": hash(ACCESS_TOKEN) })));
+        assert!(request.verify(&state, Some(ACCESS_TOKEN)).is_ok());
+    }
+
+    #[test]
+    fn ath_mismatch() {
+        let state = testing::state(&[]);
+        for overrides in [json!({}), json!({ "ath": hash("anotherAccessToken") })] {
+            let request = request(proof(overrides));
+            let result = request.verify(&state, Some(ACCESS_TOKEN));
+            assert!(matches!(result, Err(Error::Mismatch("ath"))));
+        }
+    }
+
+    #[test]
+    fn jti_replay() {
+        let state = testing::state(&[]);
+        let proof = proof(json!({}));
+        assert!(request(proof.clone()).verify(&state, None).is_ok());
+        let result = request(proof).verify(&state, None);
+        assert!(matches!(result, Err(Error::Replay(_))));
+    }
+
+    #[test]
+    fn nonce_required() {
+        let state = testing::state(&[("DPOP_NONCE_REQUIRED", "true")]);
+        let result = request(proof(json!({}))).verify(&state, None);
+        let Err(Error::UseNonce(nonce)) = result else {
+            panic!("a nonce must be required");
+        };
+        let request = request(proof(json!({ "nonce": nonce.to_string() })));
+        assert!(request.verify(&state, None).is_ok());
+    }
+
+    #[test]
+    fn unknown_nonce() {
+        let state = testing::state(&[("DPOP_NONCE_REQUIRED", "true")]);
+        let nonce = "0000000000000000000000000000000000000000";
+        let result = request(proof(json!({ "nonce": nonce }))).verify(&state, None);
+        assert!(matches!(result, Err(Error::UseNonce(_))));
+    }
+
+    #[test]
+    fn expired_nonce() {
+        let state = testing::state(&[
+            ("DPOP_NONCE_EXPIRATION", "0"),
+            ("DPOP_NONCE_REQUIRED", "true"),
+        ]);
+        let nonce = state.dpop_nonce();
+        let result = request(proof(json!({ "nonce": nonce.to_string() }))).verify(&state, None);
+        assert!(matches!(result, Err(Error::UseNonce(_))));
+    }
+
+    #[test]
+    fn private_key_rejected() {
+        let state = testing::state(&[]);
+        let key = SecretKey::random(&mut rand::thread_rng());
+        let mut jwk = jwk(&key);
+        jwk["d"] = URL_SAFE_NO_PAD.encode(key.to_bytes()).into();
+        let request = request(sign_proof(&key, jwk, json!({})));
+        assert!(matches!(
+            request.verify(&state, None),
+            Err(Error::PrivateKey)
+        ));
+    }
+}
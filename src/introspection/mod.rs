@@ -0,0 +1,225 @@
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::data::access_token::{AccessToken, Actor, Confirmation};
+use crate::data::authorization_details::AuthorizationDetail;
+use crate::data::time::UnixTime;
+use crate::data::{ErrorResponse, Scope, TokenType};
+use crate::mtls::ClientCertificate;
+use crate::state::AppState;
+use crate::token::handler::{authenticate_client, InvalidParamError};
+use crate::token::jwt;
+use axum::extract::{Form, FromRequest};
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response, Result};
+use axum::{Extension, Json};
+use jsonwebtoken::Validation;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::formats::{PreferOne, SpaceSeparator};
+use serde_with::{serde_as, DisplayFromStr, OneOrMany, StringWithSeparator};
+use std::collections::HashSet;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc7662#section-2.1
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Form))]
+pub struct IntrospectionParams {
+    #[serde(deserialize_with = "client_id")]
+    client_id: SecureString,
+    #[serde(default, deserialize_with = "client_secret")]
+    client_secret: Option<SecureString>,
+    #[serde(deserialize_with = "token")]
+    token: NonEmptyString,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7662#section-2.2
+#[derive(Serialize)]
+pub struct IntrospectionResponse {
+    active: bool,
+    #[serde(flatten)]
+    token: Option<TokenInfo>,
+}
+
+#[serde_as]
+#[derive(Serialize)]
+struct TokenInfo {
+    acr: NonEmptyString,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    act: Option<Actor>,
+    #[serde_as(as = "OneOrMany<DisplayFromStr, PreferOne>")]
+    aud: Vec<Uri>,
+    auth_time: UnixTime,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authorization_details: Vec<AuthorizationDetail>,
+    client_id: SecureString,
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-6.2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cnf: Option<Confirmation>,
+    exp: UnixTime,
+    iat: UnixTime,
+    #[serde(with = "http_serde::uri")]
+    iss: Uri,
+    jti: SecureString,
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, Scope>")]
+    scope: HashSet<Scope>,
+    sub: SecureString,
+    token_type: TokenType,
+}
+
+impl From<AccessToken> for TokenInfo {
+    fn from(token: AccessToken) -> Self {
+        let token_type = token.token_type();
+        Self {
+            acr: token.acr,
+            act: token.act,
+            aud: token.aud,
+            auth_time: token.auth_time,
+            authorization_details: token.authorization_details,
+            client_id: token.cid,
+            cnf: token.cnf,
+            exp: token.exp,
+            iat: token.iat,
+            iss: token.iss,
+            jti: token.jti,
+            scope: token.scp,
+            sub: token.sub,
+            token_type,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidClient(InvalidParamError),
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7662#section-2.3
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let response = ErrorResponse::new("invalid_client", &self);
+        (StatusCode::UNAUTHORIZED, Json(response)).into_response()
+    }
+}
+
+pub async fn introspect(
+    state: AppState,
+    certificate: Option<Extension<ClientCertificate>>,
+    params: IntrospectionParams,
+) -> Result<impl IntoResponse> {
+    let certificate = certificate
+        .as_ref()
+        .map(|Extension(certificate)| certificate);
+    authenticate_client(&state, &params.client_id, params.client_secret, certificate)
+        .map_err(Error::InvalidClient)?;
+    // Any token that cannot be verified is reported as inactive
+    let token = decode_access_token(&state, &params.token).map(TokenInfo::from);
+    Ok(Json(IntrospectionResponse {
+        active: token.is_some(),
+        token,
+    }))
+}
+
+// Access tokens may have been issued for any resource
+fn decode_access_token(state: &AppState, token: &NonEmptyString) -> Option<AccessToken> {
+    if let Some(access_token) = state.access_token(token.as_ref()) {
+        return Some(access_token);
+    }
+    let mut validation = Validation::default();
+    validation.validate_aud = false;
+    let token = jwt::decode_access_token(state, token.as_ref(), &validation).ok()?;
+    (token.iss == state.issuer() && !token.exp.expired()).then_some(token)
+}
+
+fn client_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn client_secret<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecureString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_secret`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn token<'de, D: Deserializer<'de>>(d: D) -> Result<NonEmptyString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `token`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET};
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn client_secret_not_disclosed() {
+        let state = testing::state(&[]);
+        let params = [
+            ("client_id", CLIENT_ID),
+            ("client_secret", "0000000000000000000000000000000000000000"),
+            ("token", "token"),
+        ];
+        let response = testing::post_form(&state, "/introspect", &params).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert!(!response.body.contains(CLIENT_SECRET));
+    }
+
+    async fn introspect(state: &crate::state::AppState, token: &str) -> serde_json::Value {
+        let params = [
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("token", token),
+        ];
+        let response = testing::post_form(state, "/introspect", &params).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        response.json()
+    }
+
+    #[tokio::test]
+    async fn opaque_access_token() {
+        let state = testing::state(&[("ACCESS_TOKEN_FORMAT", "opaque")]);
+        let access_token = testing::access_token(&state, CLIENT_ID).await;
+        let introspection = introspect(&state, &access_token).await;
+        assert_eq!(introspection["active"], true);
+        assert_eq!(introspection["client_id"], CLIENT_ID);
+        assert_eq!(introspection["sub"], state.get_user().id().to_string());
+    }
+
+    #[tokio::test]
+    async fn expired_opaque_access_token() {
+        let state = testing::state(&[("ACCESS_TOKEN_FORMAT", "opaque"), ("EXPIRATION", "0")]);
+        let access_token = testing::access_token(&state, CLIENT_ID).await;
+        assert_eq!(introspect(&state, &access_token).await["active"], false);
+    }
+
+    #[tokio::test]
+    async fn unknown_opaque_access_token() {
+        let state = testing::state(&[("ACCESS_TOKEN_FORMAT", "opaque")]);
+        let token = "0000000000000000000000000000000000000000";
+        assert_eq!(introspect(&state, token).await["active"], false);
+    }
+
+    #[tokio::test]
+    async fn mtls_client_authenticated() {
+        let jwks = testing::client_certificate_jwks();
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__JWKS", &jwks),
+            (
+                "CLIENTS__WEB__TOKEN_ENDPOINT_AUTH_METHOD",
+                "self_signed_tls_client_auth",
+            ),
+        ]);
+        let params = [("client_id", CLIENT_ID), ("token", "token")];
+        let certificate = testing::client_certificate();
+        let response =
+            testing::post_form_with_certificate(&state, "/introspect", &params, certificate).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.json()["active"], false);
+        let response = testing::post_form(&state, "/introspect", &params).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json()["error"], "invalid_client");
+    }
+}
@@ -0,0 +1,79 @@
+use crate::data::time::UnixTime;
+use crate::state::keys::{KeyStatus, SigningKey};
+use crate::state::AppState;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Serialize;
+
+// https://datatracker.ietf.org/doc/html/rfc7517#section-5
+#[derive(Serialize)]
+pub struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+// The tokens are signed with symmetric keys, so only their identifiers are
+// published: the secret `k` never leaves the server
+#[derive(Serialize)]
+pub struct Jwk {
+    alg: &'static str,
+    kid: String,
+    kty: &'static str,
+    #[serde(rename = "use")]
+    use_: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct KeySummary {
+    kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retires_at: Option<UnixTime>,
+    status: KeyStatus,
+}
+
+impl From<SigningKey> for Jwk {
+    fn from(key: SigningKey) -> Self {
+        Self {
+            alg: "HS256",
+            kid: key.kid,
+            kty: "oct",
+            use_: "sig",
+        }
+    }
+}
+
+impl From<SigningKey> for KeySummary {
+    fn from(key: SigningKey) -> Self {
+        Self {
+            kid: key.kid,
+            retires_at: key.retires_at,
+            status: key.status,
+        }
+    }
+}
+
+pub async fn jwks(state: AppState) -> impl IntoResponse {
+    let keys = state.keys().into_iter().map(Jwk::from).collect();
+    Json(JwkSet { keys })
+}
+
+pub async fn rotate(state: AppState) -> impl IntoResponse {
+    state.rotate_keys();
+    let keys: Vec<KeySummary> = state.keys().into_iter().map(KeySummary::from).collect();
+    Json(keys)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn secrets_not_published() {
+        let state = testing::state(&[]);
+        let response = testing::get(&state, "/keys").await;
+        assert_eq!(response.status, StatusCode::OK);
+        let keys = response.json()["keys"].as_array().unwrap().clone();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.iter().all(|key| key.get("k").is_none()));
+    }
+}
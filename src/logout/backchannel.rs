@@ -0,0 +1,105 @@
+use crate::bounded_string::SecureString;
+use crate::data::time::UnixTime;
+use crate::state::session::{DeliveryAttempt, LogoutDelivery};
+use crate::state::AppState;
+use crate::token::jwt;
+use axum::http::Uri;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::time::sleep;
+
+// https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken
+const LOGOUT_EVENT: &str = "http://schemas.openid.net/event/backchannel-logout";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct LogoutToken {
+    aud: SecureString,
+    events: Value,
+    exp: UnixTime,
+    iat: UnixTime,
+    #[serde(with = "http_serde::uri")]
+    iss: Uri,
+    jti: SecureString,
+    sid: SecureString,
+    sub: SecureString,
+}
+
+pub fn logout_token(
+    state: &AppState,
+    client_id: &SecureString,
+    sid: &SecureString,
+    user_id: &SecureString,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let now = UnixTime::now();
+    let logout_token = LogoutToken {
+        aud: client_id.clone(),
+        events: json!({ LOGOUT_EVENT: {} }),
+        exp: now + state.expiration(),
+        iat: now,
+        iss: state.issuer(),
+        jti: state.gen_secure_string(),
+        sid: sid.clone(),
+        sub: user_id.clone(),
+    };
+    jwt::encode_logout_token(logout_token, state.signing_key())
+}
+
+// https://openid.net/specs/openid-connect-backchannel-1_0.html#BCRequest
+pub async fn deliver(state: AppState, delivery: LogoutDelivery, logout_token: String) {
+    let uri = delivery.uri.to_string();
+    let index = state.add_logout_delivery(delivery);
+    let client = match reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(e) => {
+            let attempt = DeliveryAttempt {
+                at: UnixTime::now(),
+                error: Some(e.to_string()),
+                status: None,
+            };
+            state.record_logout_attempt(index, attempt, false);
+            return;
+        }
+    };
+    for attempt in 0..state.backchannel_logout_attempts() {
+        if attempt > 0 {
+            sleep(RETRY_DELAY * 2u32.pow(attempt - 1)).await;
+        }
+        let response = client
+            .post(&uri)
+            .form(&[("logout_token", &logout_token)])
+            .send()
+            .await;
+        let (status, error) = match response {
+            Ok(response) => (Some(response.status()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let delivered = status.is_some_and(|status| status.is_success());
+        let attempt = DeliveryAttempt {
+            at: UnixTime::now(),
+            error,
+            status: status.map(|status| status.as_u16()),
+        };
+        state.record_logout_attempt(index, attempt, delivered);
+        if delivered {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn logout_token_typed() {
+        let state = testing::state(&[]);
+        let id = SecureString::try_from("0jrabyQWm4B9zVJPbotY").unwrap();
+        let logout_token = logout_token(&state, &id, &id, &id).unwrap();
+        let header = jsonwebtoken::decode_header(&logout_token).unwrap();
+        assert_eq!(header.typ.as_deref(), Some("logout+jwt"));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::bounded_string::{NonEmptyString, SecureString};
+use axum::http::Uri;
+use serde::{Deserialize, Deserializer};
+
+// https://openid.net/specs/openid-connect-rpinitiated-1_0.html#RPLogout
+#[derive(Deserialize)]
+pub struct EndSessionParams {
+    #[serde(default, deserialize_with = "client_id")]
+    pub client_id: Option<SecureString>,
+    #[serde(default, deserialize_with = "id_token_hint")]
+    pub id_token_hint: Option<NonEmptyString>,
+    #[serde(default, deserialize_with = "post_logout_redirect_uri")]
+    pub post_logout_redirect_uri: Option<Uri>,
+    #[serde(default, deserialize_with = "state")]
+    pub state: Option<NonEmptyString>,
+}
+
+fn client_id<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecureString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map(Some)
+        .map_err(|e| format!("error while parsing field `client_id`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn id_token_hint<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NonEmptyString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map(Some)
+        .map_err(|e| format!("error while parsing field `id_token_hint`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn post_logout_redirect_uri<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Uri>, D::Error> {
+    http_serde::uri::deserialize(d)
+        .map(Some)
+        .map_err(|e| format!("error while parsing field `post_logout_redirect_uri`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn state<'de, D: Deserializer<'de>>(d: D) -> Result<Option<NonEmptyString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map(Some)
+        .map_err(|e| format!("error while parsing field `state`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
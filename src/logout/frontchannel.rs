@@ -0,0 +1,26 @@
+use crate::bounded_string::SecureString;
+use crate::state::client::Client;
+use crate::state::AppState;
+
+// https://openid.net/specs/openid-connect-frontchannel-1_0.html#RPLogout
+pub fn logout_uri(
+    state: &AppState,
+    client: &Client,
+    sid: &SecureString,
+) -> Result<Option<String>, serde_urlencoded::ser::Error> {
+    let Some(uri) = client.frontchannel_logout_uri.as_ref() else {
+        return Ok(None);
+    };
+    if !client.frontchannel_logout_session_required {
+        return Ok(Some(uri.to_string()));
+    }
+    let params = [
+        ("iss", state.issuer().to_string()),
+        ("sid", sid.to_string()),
+    ];
+    let encoded = serde_urlencoded::to_string(params)?;
+    match uri.query() {
+        Some(_) => Ok(Some(format!("{uri}&{encoded}"))),
+        None => Ok(Some(format!("{uri}?{encoded}"))),
+    }
+}
@@ -0,0 +1,313 @@
+use super::extractor::EndSessionParams;
+use super::{backchannel, frontchannel};
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::state::session::{expired_session_cookie, LogoutDelivery, UserSession, SESSION_COOKIE};
+use crate::state::AppState;
+use crate::token::jwt;
+use askama::Template;
+use axum::extract::{Form, Query};
+use axum::http::header::{InvalidHeaderValue, LOCATION, SET_COOKIE};
+use axum::http::{HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response, Result};
+use axum::Json;
+use axum_extra::TypedHeader;
+use headers::Cookie;
+use jsonwebtoken::{Algorithm, Validation};
+use serde::Deserialize;
+use thiserror::Error;
+
+#[derive(Template)]
+#[template(path = "logout.html")]
+pub struct LogoutPage {
+    logout_uris: Vec<String>,
+    redirect_uri: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IdTokenHint {
+    #[serde(default)]
+    aud: Option<SecureString>,
+    #[serde(default)]
+    sid: Option<SecureString>,
+    sub: SecureString,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid id_token_hint: {0}")]
+    IdTokenHint(#[from] jwt::DecodeError),
+    #[error("`post_logout_redirect_uri` requires `client_id` or `id_token_hint`")]
+    UnknownClient,
+    #[error("`post_logout_redirect_uri` `{0}` is not registered for client `{1}`")]
+    PostLogoutRedirectUri(Box<Uri>, SecureString),
+    #[error("jwt encode error: `{0}`")]
+    Jwt(#[source] jsonwebtoken::errors::Error),
+    #[error("cannot encode the redirection parameters: {0}")]
+    Encode(#[from] serde_urlencoded::ser::Error),
+    #[error("invalid redirection uri: {0}")]
+    Location(#[from] InvalidHeaderValue),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let response = match self {
+            Self::Jwt(_) | Self::Encode(_) | Self::Location(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
+            }
+            _ => (StatusCode::BAD_REQUEST, self.to_string()),
+        };
+        response.into_response()
+    }
+}
+
+pub async fn end_session(
+    state: AppState,
+    cookie: Option<TypedHeader<Cookie>>,
+    Query(params): Query<EndSessionParams>,
+) -> Result<Response> {
+    Ok(logout(&state, cookie, params)?)
+}
+
+pub async fn end_session_form(
+    state: AppState,
+    cookie: Option<TypedHeader<Cookie>>,
+    Form(params): Form<EndSessionParams>,
+) -> Result<Response> {
+    Ok(logout(&state, cookie, params)?)
+}
+
+pub async fn deliveries(state: AppState) -> impl IntoResponse {
+    Json(state.logout_deliveries())
+}
+
+// https://openid.net/specs/openid-connect-rpinitiated-1_0.html#RPLogout
+fn logout(
+    state: &AppState,
+    cookie: Option<TypedHeader<Cookie>>,
+    params: EndSessionParams,
+) -> Result<Response, Error> {
+    let hint = params
+        .id_token_hint
+        .map(|id_token_hint| decode_id_token_hint(state, id_token_hint.as_ref()))
+        .transpose()?;
+    // The redirection URI must be registered by the client the user logs out of
+    let client_id = params
+        .client_id
+        .or_else(|| hint.as_ref().and_then(|hint| hint.aud.clone()));
+    let redirect_uri = match params.post_logout_redirect_uri {
+        Some(uri) => {
+            let client = state.client(&client_id.ok_or(Error::UnknownClient)?);
+            if !client.allows_post_logout_redirect_uri(&uri) {
+                return Err(Error::PostLogoutRedirectUri(uri.into(), client.client_id));
+            }
+            Some(post_logout_redirect_uri(uri, params.state)?)
+        }
+        None => None,
+    };
+    // Without a hint the session of the browser is ended
+    let sid = match hint {
+        Some(hint) => hint.sid.or_else(|| state.find_session(&hint.sub)),
+        None => cookie
+            .as_ref()
+            .and_then(|TypedHeader(cookie)| cookie.get(SESSION_COOKIE))
+            .and_then(|sid| SecureString::try_from(sid).ok()),
+    };
+    let mut logout_uris = Vec::new();
+    let session = sid.and_then(|sid| state.end_session(&sid).map(|session| (sid, session)));
+    if let Some((sid, session)) = session {
+        backchannel_logout(state, &sid, &session)?;
+        logout_uris = frontchannel_logout_uris(state, &sid, &session)?;
+    }
+    let cookie = [(SET_COOKIE, expired_session_cookie())];
+    // https://openid.net/specs/openid-connect-frontchannel-1_0.html#OPLogout
+    if !logout_uris.is_empty() {
+        let page = LogoutPage {
+            logout_uris,
+            redirect_uri,
+        };
+        return Ok((cookie, page).into_response());
+    }
+    match redirect_uri {
+        Some(location) => {
+            let location = HeaderValue::from_str(&location)?;
+            Ok((StatusCode::FOUND, cookie, [(LOCATION, location)]).into_response())
+        }
+        None => Ok((StatusCode::OK, cookie, "logged out").into_response()),
+    }
+}
+
+fn post_logout_redirect_uri(uri: Uri, state: Option<NonEmptyString>) -> Result<String, Error> {
+    let Some(state) = state else {
+        return Ok(uri.to_string());
+    };
+    let encoded = serde_urlencoded::to_string([("state", state.to_string())])?;
+    match uri.query() {
+        Some(_) => Ok(format!("{uri}&{encoded}")),
+        None => Ok(format!("{uri}?{encoded}")),
+    }
+}
+
+// The hint is usually an expired id token issued to the client
+fn decode_id_token_hint(state: &AppState, token: &str) -> Result<IdTokenHint, Error> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.required_spec_claims.clear();
+    validation.validate_aud = false;
+    validation.validate_exp = false;
+    Ok(jwt::decode(state, token, &validation)?)
+}
+
+// https://openid.net/specs/openid-connect-backchannel-1_0.html#BCSupport
+fn backchannel_logout(
+    state: &AppState,
+    sid: &SecureString,
+    session: &UserSession,
+) -> Result<(), Error> {
+    for client_id in &session.clients {
+        let Some(uri) = state.client(client_id).backchannel_logout_uri else {
+            continue;
+        };
+        let logout_token = backchannel::logout_token(state, client_id, sid, &session.user_id)
+            .map_err(Error::Jwt)?;
+        let delivery = LogoutDelivery {
+            attempts: Vec::new(),
+            client_id: client_id.clone(),
+            delivered: false,
+            sid: sid.clone(),
+            uri,
+        };
+        tokio::spawn(backchannel::deliver(state.clone(), delivery, logout_token));
+    }
+    Ok(())
+}
+
+fn frontchannel_logout_uris(
+    state: &AppState,
+    sid: &SecureString,
+    session: &UserSession,
+) -> Result<Vec<String>, Error> {
+    let mut logout_uris = Vec::new();
+    for client_id in &session.clients {
+        if let Some(uri) = frontchannel::logout_uri(state, &state.client(client_id), sid)? {
+            logout_uris.push(uri);
+        }
+    }
+    Ok(logout_uris)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET, CODE_VERIFIER, REDIRECT_URI};
+    use axum::http::StatusCode;
+
+    const LOGOUT_URI: &str = "http://localhost:3000/logout";
+
+    fn state() -> crate::state::AppState {
+        testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__POST_LOGOUT_REDIRECT_URIS", LOGOUT_URI),
+        ])
+    }
+
+    async fn id_token(state: &crate::state::AppState) -> String {
+        let code = testing::authorization_code(state, CLIENT_ID).await;
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("code", &code),
+            ("code_verifier", CODE_VERIFIER),
+            ("redirect_uri", REDIRECT_URI),
+        ];
+        let response = testing::post_form(state, "/token", &params).await;
+        response.json()["id_token"].as_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn redirect_with_encoded_state() {
+        let state = state();
+        let params = [
+            ("client_id", CLIENT_ID),
+            ("post_logout_redirect_uri", LOGOUT_URI),
+            ("state", "a&b=c d"),
+        ];
+        let response = testing::post_form(&state, "/logout", &params).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert_eq!(
+            response.header("location"),
+            format!("{LOGOUT_URI}?state=a%26b%3Dc+d")
+        );
+    }
+
+    #[tokio::test]
+    async fn client_identified_by_id_token_hint() {
+        let state = state();
+        let id_token = id_token(&state).await;
+        let params = [
+            ("id_token_hint", id_token.as_str()),
+            ("post_logout_redirect_uri", LOGOUT_URI),
+        ];
+        let response = testing::post_form(&state, "/logout", &params).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert_eq!(response.header("location"), LOGOUT_URI);
+    }
+
+    #[tokio::test]
+    async fn frontchannel_page_escapes_state() {
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            (
+                "CLIENTS__WEB__FRONTCHANNEL_LOGOUT_URI",
+                "http://localhost:3000/frontchannel",
+            ),
+        ]);
+        let id_token = id_token(&state).await;
+        let params = [
+            ("id_token_hint", id_token.as_str()),
+            ("post_logout_redirect_uri", LOGOUT_URI),
+            ("state", "';alert(1);'\"><script>alert(2)</script>"),
+        ];
+        let response = testing::post_form(&state, "/logout", &params).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.body.contains("http://localhost:3000/frontchannel"));
+        // The redirection waits for the iframes to load
+        assert!(response.body.contains("window.onload"));
+        assert!(!response.body.contains(r#"http-equiv="refresh""#));
+        assert!(response
+            .body
+            .contains(r#"data-redirect-uri="http://localhost:3000/logout?"#));
+        assert!(!response.body.contains("<script>alert"));
+        assert!(!response.body.contains("';alert(1);'"));
+        assert!(response
+            .body
+            .contains("state=%27%3Balert%281%29%3B%27%22%3E%3Cscript%3E"));
+    }
+
+    #[tokio::test]
+    async fn unregistered_redirect_uri_rejected() {
+        let state = state();
+        let params = [
+            ("client_id", CLIENT_ID),
+            ("post_logout_redirect_uri", "https://attacker.example.com/"),
+        ];
+        let response = testing::post_form(&state, "/logout", &params).await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+    }
+
+    #[tokio::test]
+    async fn redirect_requires_client() {
+        let state = state();
+        let params = [("post_logout_redirect_uri", LOGOUT_URI)];
+        let response = testing::post_form(&state, "/logout", &params).await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+    }
+}
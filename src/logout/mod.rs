@@ -0,0 +1,4 @@
+pub mod backchannel;
+pub mod extractor;
+pub mod frontchannel;
+pub mod handler;
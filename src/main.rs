@@ -1,23 +1,48 @@
+mod authentication;
 mod authorize;
 mod bounded_string;
+mod ciba;
 mod config;
+mod consent;
 mod crypto;
 mod data;
+mod device;
+mod dpop;
+mod introspection;
+mod keys;
+mod logout;
+mod mtls;
+mod par;
+mod registration;
 mod state;
 mod token;
 mod userinfo;
 
+#[cfg(test)]
+mod testing;
+
+use authentication::{account, authentication, otp, set_authentication};
 use authorize::handler::authorize;
 use axum::http::StatusCode;
 use axum::routing::post;
 use axum::{routing::get, Router};
+use ciba::handler::{backchannel_authentication, pending, resolve};
 use config::Configuration;
+use device::handler::{device_authorization, verification_page, verify};
+use introspection::introspect;
+use keys::{jwks, rotate};
+use logout::handler::{deliveries, end_session, end_session_form};
+use mtls::server::TlsServer;
+use par::handler::par;
+use registration::handler::{delete_client, read_client, register, update_client};
 use state::AppState;
 use std::net::SocketAddr;
+use std::time::Duration;
 use token::handler::token;
 use tokio::net::TcpListener;
 use tokio::signal::ctrl_c;
 use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::interval;
 use userinfo::userinfo;
 
 async fn shutdown_signal() {
@@ -33,17 +58,72 @@ async fn shutdown_signal() {
     }
 }
 
+async fn rotate_keys(state: AppState, period: u64) {
+    let mut interval = interval(Duration::from_secs(period));
+    // The first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        state.rotate_keys();
+    }
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route(
+            "/admin/authentication",
+            get(authentication).put(set_authentication),
+        )
+        .route("/admin/ciba", get(pending).post(resolve))
+        .route("/admin/keys/rotate", post(rotate))
+        .route("/admin/logout/deliveries", get(deliveries))
+        .route("/authorize", get(authorize))
+        .route("/bc-authorize", post(backchannel_authentication))
+        .route("/consent", post(consent::consent))
+        .route("/device", get(verification_page).post(verify))
+        .route("/device_authorization", post(device_authorization))
+        .route("/health", get(|| async { StatusCode::OK }))
+        .route("/introspect", post(introspect))
+        .route("/keys", get(jwks))
+        .route("/login/account", post(account::select))
+        .route("/login/otp", post(otp::verify))
+        .route("/logout", get(end_session).post(end_session_form))
+        .route("/par", post(par))
+        .route("/register", post(register))
+        .route(
+            "/register/:client_id",
+            get(read_client).put(update_client).delete(delete_client),
+        )
+        .route("/token", post(token))
+        .route("/userinfo", get(userinfo))
+        .with_state(state)
+}
+
 #[tokio::main]
 async fn main() {
     let config = Configuration::new();
     let address: SocketAddr = ([0, 0, 0, 0], config.port).into();
+    let key_rotation_interval = config.key_rotation_interval;
+    let tls_address: SocketAddr = ([0, 0, 0, 0], config.tls_port).into();
+    let tls_files = config
+        .tls_certificate
+        .clone()
+        .zip(config.tls_private_key.clone());
+    let tls_client_ca = config.tls_client_ca.clone();
     let state = AppState::from(config);
-    let router = Router::new()
-        .route("/authorize", get(authorize))
-        .route("/health", get(|| async { StatusCode::OK }))
-        .route("/token", post(token))
-        .route("/userinfo", get(userinfo))
-        .with_state(state);
+    if key_rotation_interval > 0 {
+        tokio::spawn(rotate_keys(state.clone(), key_rotation_interval));
+    }
+    let router = router(state);
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-5
+    if tls_address.port() > 0 {
+        let (certificate, private_key) =
+            tls_files.expect("TLS_CERTIFICATE and TLS_PRIVATE_KEY are required with TLS_PORT");
+        let server = TlsServer::new(&certificate, &private_key, tls_client_ca.as_deref())
+            .unwrap_or_else(|e| panic!("{e}"));
+        let listener = TcpListener::bind(tls_address).await.unwrap();
+        tokio::spawn(server.serve(router.clone(), listener));
+    }
     let listener = TcpListener::bind(address).await.unwrap();
     axum::serve(listener, router)
         .with_graceful_shutdown(shutdown_signal())
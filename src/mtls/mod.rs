@@ -0,0 +1,361 @@
+pub mod server;
+
+use crate::bounded_string::SecureString;
+use crate::crypto::hash;
+use crate::state::client::Client;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rustls::client::VerifierBuilderError;
+use rustls::pki_types::{CertificateDer, UnixTime};
+use rustls::server::danger::ClientCertVerifier;
+use rustls::server::WebPkiClientVerifier;
+use rustls::RootCertStore;
+use simple_asn1::{from_der, ASN1Block};
+use std::sync::Arc;
+use thiserror::Error;
+
+// The DER encoded certificate the client presented during the TLS handshake
+#[derive(Clone)]
+pub struct ClientCertificate {
+    pub der: Vec<u8>,
+    // Whether the presented chain leads to one of the `TLS_CLIENT_CA`
+    pub trusted: bool,
+}
+
+// The certificate authorities that issue the certificates of `tls_client_auth`
+// https://datatracker.ietf.org/doc/html/rfc8705#section-2.1
+#[derive(Clone)]
+pub struct ClientCa(Arc<dyn ClientCertVerifier>);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("client `{0}` must authenticate with a TLS client certificate")]
+    MissingCertificate(SecureString),
+    #[error("client `{0}` has no registered `tls_client_auth_subject_dn`")]
+    MissingSubjectDn(SecureString),
+    #[error("certificate subject `{1}` does not match `{0}`")]
+    SubjectDn(String, String),
+    #[error("certificate is not issued by a trusted certificate authority")]
+    UntrustedCertificate,
+    #[error("certificate is not one of the keys registered by client `{0}`")]
+    UnregisteredCertificate(SecureString),
+    #[error("access token is bound to another TLS client certificate")]
+    CertificateMismatch,
+}
+
+impl ClientCa {
+    pub fn new(roots: Vec<CertificateDer<'static>>) -> Result<Self, VerifierBuilderError> {
+        let mut store = RootCertStore::empty();
+        store.add_parsable_certificates(roots);
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(store), provider)
+            .allow_unauthenticated()
+            .build()?;
+        Ok(Self(verifier))
+    }
+
+    // The end entity certificate comes first, followed by the intermediates
+    pub fn verifies(&self, chain: &[CertificateDer<'_>]) -> bool {
+        let Some((end_entity, intermediates)) = chain.split_first() else {
+            return false;
+        };
+        self.0
+            .verify_client_cert(end_entity, intermediates, UnixTime::now())
+            .is_ok()
+    }
+}
+
+impl ClientCertificate {
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-3.1
+    pub fn thumbprint(&self) -> String {
+        hash(&self.der)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc4514#section-2
+    pub fn subject_dn(&self) -> Option<String> {
+        let blocks = from_der(&self.der).ok()?;
+        let Some(ASN1Block::Sequence(_, certificate)) = blocks.first() else {
+            return None;
+        };
+        let Some(ASN1Block::Sequence(_, tbs)) = certificate.first() else {
+            return None;
+        };
+        // The version is an optional explicitly tagged field before the serial number
+        let index = match tbs.first() {
+            Some(ASN1Block::Explicit(..)) => 5,
+            _ => 4,
+        };
+        let Some(ASN1Block::Sequence(_, rdns)) = tbs.get(index) else {
+            return None;
+        };
+        let rdns = rdns.iter().rev().filter_map(|rdn| match rdn {
+            ASN1Block::Set(_, attributes) => {
+                let attributes = attributes.iter().filter_map(attribute);
+                Some(attributes.collect::<Vec<_>>().join("+"))
+            }
+            _ => None,
+        });
+        Some(rdns.collect::<Vec<_>>().join(","))
+    }
+}
+
+fn attribute(block: &ASN1Block) -> Option<String> {
+    let ASN1Block::Sequence(_, pair) = block else {
+        return None;
+    };
+    let [ASN1Block::ObjectIdentifier(_, oid), value] = pair.as_slice() else {
+        return None;
+    };
+    let oid = oid.as_vec::<u64>().ok()?;
+    let name = match oid.as_slice() {
+        [2, 5, 4, 3] => "CN".to_string(),
+        [2, 5, 4, 6] => "C".to_string(),
+        [2, 5, 4, 7] => "L".to_string(),
+        [2, 5, 4, 8] => "ST".to_string(),
+        [2, 5, 4, 9] => "STREET".to_string(),
+        [2, 5, 4, 10] => "O".to_string(),
+        [2, 5, 4, 11] => "OU".to_string(),
+        [0, 9, 2342, 19200300, 100, 1, 1] => "UID".to_string(),
+        [0, 9, 2342, 19200300, 100, 1, 25] => "DC".to_string(),
+        _ => oid.iter().map(u64::to_string).collect::<Vec<_>>().join("."),
+    };
+    let value = match value {
+        ASN1Block::UTF8String(_, value)
+        | ASN1Block::PrintableString(_, value)
+        | ASN1Block::TeletexString(_, value)
+        | ASN1Block::IA5String(_, value)
+        | ASN1Block::UniversalString(_, value)
+        | ASN1Block::BMPString(_, value) => value,
+        _ => return None,
+    };
+    Some(format!("{name}={}", escape(value)))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc4514#section-2.4
+fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for (index, c) in value.chars().enumerate() {
+        let leading = index == 0 && (c == ' ' || c == '#');
+        let trailing = index == value.chars().count() - 1 && c == ' ';
+        if leading || trailing || matches!(c, '"' | '+' | ',' | ';' | '<' | '>' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn presented<'a>(
+    client: &Client,
+    certificate: Option<&'a ClientCertificate>,
+) -> Result<&'a ClientCertificate, Error> {
+    certificate.ok_or_else(|| Error::MissingCertificate(client.client_id.clone()))
+}
+
+// The certificate chains to a trusted authority and has the registered subject
+// https://datatracker.ietf.org/doc/html/rfc8705#section-2.1.2
+pub fn tls_client_auth(
+    client: &Client,
+    certificate: Option<&ClientCertificate>,
+) -> Result<(), Error> {
+    let certificate = presented(client, certificate)?;
+    let expected = client
+        .tls_client_auth_subject_dn
+        .as_ref()
+        .ok_or_else(|| Error::MissingSubjectDn(client.client_id.clone()))?;
+    if !certificate.trusted {
+        return Err(Error::UntrustedCertificate);
+    }
+    let subject = certificate.subject_dn().unwrap_or_default();
+    if *expected == subject {
+        Ok(())
+    } else {
+        Err(Error::SubjectDn(expected.clone(), subject))
+    }
+}
+
+// The certificate is one of the keys registered by the client
+// https://datatracker.ietf.org/doc/html/rfc8705#section-2.2.2
+pub fn self_signed_tls_client_auth(
+    client: &Client,
+    certificate: Option<&ClientCertificate>,
+) -> Result<(), Error> {
+    let certificate = presented(client, certificate)?;
+    let thumbprint = certificate.thumbprint();
+    let registered = client.jwks.iter().flat_map(|jwks| &jwks.keys).any(|jwk| {
+        jwk.common.x509_sha256_fingerprint.as_ref() == Some(&thumbprint)
+            || jwk
+                .common
+                .x509_chain
+                .as_ref()
+                .and_then(|chain| chain.first())
+                == Some(&STANDARD.encode(&certificate.der))
+    });
+    if registered {
+        Ok(())
+    } else {
+        Err(Error::UnregisteredCertificate(client.client_id.clone()))
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8705#section-3
+pub fn verify_binding(
+    x5t: Option<&str>,
+    certificate: Option<&ClientCertificate>,
+) -> Result<(), Error> {
+    match x5t {
+        Some(x5t) if certificate.map(ClientCertificate::thumbprint).as_deref() != Some(x5t) => {
+            Err(Error::CertificateMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    const CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIB2zCCAYGgAwIBAgIUaCvrILOydbZkJvXu7I4dV+i9nCIwCgYIKoZIzj0EAwIw
+QjELMAkGA1UEBhMCVVMxFjAUBgNVBAoMDUV4YW1wbGUsIEluYy4xGzAZBgNVBAMM
+EmNsaWVudC5leGFtcGxlLmNvbTAgFw0yNjEwMTkwNzE4MzhaGA8yMTI2MDkyNTA3
+MTgzOFowQjELMAkGA1UEBhMCVVMxFjAUBgNVBAoMDUV4YW1wbGUsIEluYy4xGzAZ
+BgNVBAMMEmNsaWVudC5leGFtcGxlLmNvbTBZMBMGByqGSM49AgEGCCqGSM49AwEH
+A0IABDul0kTu2jxMucxB2OFvpxLo1l7lZhg1hVvHV4pUeo6mqlBFgf4MgjDUR1zV
+la7sI/06mwFR6pzbW8bCsllm7TyjUzBRMB0GA1UdDgQWBBQzrP3+G+u+hAOgAJn5
+uw4dK3EqKDAfBgNVHSMEGDAWgBQzrP3+G+u+hAOgAJn5uw4dK3EqKDAPBgNVHRMB
+Af8EBTADAQH/MAoGCCqGSM49BAMCA0gAMEUCICcSy2/r5ZyTdHOKmuRgHKWk5NmE
+/t8PHYlVYgMJEL4kAiEAsDVEyx/nOo6LLj0iznlvwK+msWlrS/VADmJSyGRHCYY=
+-----END CERTIFICATE-----";
+
+    const THUMBPRINT: &str = "ncGM-z0JKSxmT1cQFbNX6wv5dCA6REhErNs1VvGDBTw";
+
+    const CA_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBpDCCAUugAwIBAgIUU+Nmu6kJWI80272nuMiVclhFLfAwCgYIKoZIzj0EAwIw
+JzEQMA4GA1UECgwHRXhhbXBsZTETMBEGA1UEAwwKRXhhbXBsZSBDQTAgFw0yNjEw
+MTkwODM3MTZaGA8yMTI2MDkyNTA4MzcxNlowJzEQMA4GA1UECgwHRXhhbXBsZTET
+MBEGA1UEAwwKRXhhbXBsZSBDQTBZMBMGByqGSM49AgEGCCqGSM49AwEHA0IABFx/
+zssNTjX4Zw2hyALHR1obJq3k6Y7B1NTXkDAQnyZlIf73Yv1L+XgLZNVbiUjWPfjY
+NNlmCSHlmYYpTxX+MCmjUzBRMB0GA1UdDgQWBBQ4u6Et3Igc3qIazqBYtxoN+bwv
+XDAfBgNVHSMEGDAWgBQ4u6Et3Igc3qIazqBYtxoN+bwvXDAPBgNVHRMBAf8EBTAD
+AQH/MAoGCCqGSM49BAMCA0cAMEQCIBNkS4UBma+QGv2PTfkehL3l03RWkYkem4XA
+fmXj2SLIAiAh7uz9p/Tt6aRvpOGrObwuu1hjBo17w3QAnZc5CP4zUw==
+-----END CERTIFICATE-----";
+
+    // Issued by `CA_CERTIFICATE` for client authentication
+    const ISSUED_CERTIFICATE: &str = "-----BEGIN CERTIFICATE-----
+MIIBvTCCAWKgAwIBAgIULGmvbR9A1vfBKpWIEMO+Jbt+D70wCgYIKoZIzj0EAwIw
+JzEQMA4GA1UECgwHRXhhbXBsZTETMBEGA1UEAwwKRXhhbXBsZSBDQTAgFw0yNjEw
+MTkwODM3MTZaGA8yMTI2MDkyNTA4MzcxNlowLzEQMA4GA1UECgwHRXhhbXBsZTEb
+MBkGA1UEAwwSY2xpZW50LmV4YW1wbGUuY29tMFkwEwYHKoZIzj0CAQYIKoZIzj0D
+AQcDQgAEH58dWKGPrFwMH6toihf+n7ThXLqncX0G/vfZop3oCF1Ct02lTOLZAsAD
+4+yu1FKGjYCIIscBUuHm5f56tOOp9qNiMGAwCQYDVR0TBAIwADATBgNVHSUEDDAK
+BggrBgEFBQcDAjAdBgNVHQ4EFgQUsLY9MyIgxR6tiFPKpgQUPYx6yIowHwYDVR0j
+BBgwFoAUOLuhLdyIHN6iGs6gWLcaDfm8L1wwCgYIKoZIzj0EAwIDSQAwRgIhAPsv
+mWccO0k021XIWpYj12HNu7hvxut/cTsDTkywIctGAiEAl7worO3S4hqDfWwxRMGt
+M32wOePlXs2cbes2CWTvNpk=
+-----END CERTIFICATE-----";
+
+    fn der(pem: &str) -> CertificateDer<'static> {
+        rustls_pemfile::certs(&mut pem.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap()
+    }
+
+    fn certificate() -> ClientCertificate {
+        ClientCertificate {
+            der: der(CERTIFICATE).to_vec(),
+            trusted: false,
+        }
+    }
+
+    fn issued_certificate() -> ClientCertificate {
+        let client_ca = ClientCa::new(vec![der(CA_CERTIFICATE)]).unwrap();
+        ClientCertificate {
+            der: der(ISSUED_CERTIFICATE).to_vec(),
+            trusted: client_ca.verifies(&[der(ISSUED_CERTIFICATE)]),
+        }
+    }
+
+    fn client() -> Client {
+        Client::unregistered("0oa1b2c3d4e5f6g7h8i9j0".try_into().unwrap())
+    }
+
+    #[test]
+    fn subject_dn() {
+        assert_eq!(
+            certificate().subject_dn().unwrap(),
+            r"CN=client.example.com,O=Example\, Inc.,C=US"
+        );
+    }
+
+    #[test]
+    fn thumbprint() {
+        assert_eq!(certificate().thumbprint(), THUMBPRINT);
+    }
+
+    #[test]
+    fn client_ca() {
+        let client_ca = ClientCa::new(vec![der(CA_CERTIFICATE)]).unwrap();
+        assert!(client_ca.verifies(&[der(ISSUED_CERTIFICATE)]));
+        assert!(!client_ca.verifies(&[der(CERTIFICATE)]));
+        assert!(!client_ca.verifies(&[]));
+    }
+
+    #[test]
+    fn tls_client_auth_subject_dn() {
+        let mut client = client();
+        client.tls_client_auth_subject_dn = Some("CN=client.example.com,O=Example".into());
+        assert!(tls_client_auth(&client, Some(&issued_certificate())).is_ok());
+        assert!(matches!(
+            tls_client_auth(&client, None),
+            Err(Error::MissingCertificate(_))
+        ));
+        client.tls_client_auth_subject_dn = Some("CN=other.example.com".into());
+        assert!(matches!(
+            tls_client_auth(&client, Some(&issued_certificate())),
+            Err(Error::SubjectDn(..))
+        ));
+    }
+
+    #[test]
+    fn tls_client_auth_untrusted_chain() {
+        let mut client = client();
+        client.tls_client_auth_subject_dn =
+            Some(r"CN=client.example.com,O=Example\, Inc.,C=US".into());
+        assert!(matches!(
+            tls_client_auth(&client, Some(&certificate())),
+            Err(Error::UntrustedCertificate)
+        ));
+    }
+
+    #[test]
+    fn self_signed_registered_certificate() {
+        let mut client = client();
+        assert!(matches!(
+            self_signed_tls_client_auth(&client, Some(&certificate())),
+            Err(Error::UnregisteredCertificate(_))
+        ));
+        let jwks = json!({"keys": [{
+            "kty": "EC",
+            "crv": "P-256",
+            "x": "O6XSRO7aPEy5zEHY4W-nEujWXuVmGDWFW8dXilR6jqY",
+            "y": "qlBFgf4MgjDUR1zVla7sI_06mwFR6pzbW8bCsllm7Tw",
+            "x5t#S256": THUMBPRINT,
+        }]});
+        client.jwks = Some(serde_json::from_value(jwks).unwrap());
+        assert!(self_signed_tls_client_auth(&client, Some(&certificate())).is_ok());
+    }
+
+    #[test]
+    fn binding() {
+        assert!(verify_binding(None, None).is_ok());
+        assert!(verify_binding(Some(THUMBPRINT), Some(&certificate())).is_ok());
+        assert!(matches!(
+            verify_binding(Some(THUMBPRINT), None),
+            Err(Error::CertificateMismatch)
+        ));
+    }
+}
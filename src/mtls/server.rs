@@ -0,0 +1,174 @@
+use super::{ClientCa, ClientCertificate};
+use axum::{Extension, Router};
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use rustls::client::danger::HandshakeSignatureValid;
+use rustls::client::VerifierBuilderError;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{DigitallySignedStruct, DistinguishedName, ServerConfig, SignatureScheme};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("cannot read `{0}`: {1}")]
+    Read(PathBuf, #[source] std::io::Error),
+    #[error("no certificate in `{0}`")]
+    MissingCertificate(PathBuf),
+    #[error("no private key in `{0}`")]
+    MissingPrivateKey(PathBuf),
+    #[error("invalid TLS configuration: {0}")]
+    Tls(#[from] rustls::Error),
+    #[error("invalid client certificate authorities: {0}")]
+    ClientCa(#[from] VerifierBuilderError),
+}
+
+// The TLS listener, along with the authorities that issue the certificates of
+// the clients registered with `tls_client_auth`
+pub struct TlsServer {
+    acceptor: TlsAcceptor,
+    client_ca: Option<ClientCa>,
+}
+
+// Client certificates are requested but not required, and any certificate is
+// accepted as long as the client proves possession of its private key: trust is
+// established per client at the token endpoint
+// https://datatracker.ietf.org/doc/html/rfc8705#section-2
+#[derive(Debug)]
+struct AnyClientCertificate(Arc<CryptoProvider>);
+
+impl ClientCertVerifier for AnyClientCertificate {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn reader(path: &Path) -> Result<BufReader<File>, Error> {
+    let file = File::open(path).map_err(|e| Error::Read(path.to_path_buf(), e))?;
+    Ok(BufReader::new(file))
+}
+
+fn certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certificates = rustls_pemfile::certs(&mut reader(path)?)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Read(path.to_path_buf(), e))?;
+    if certificates.is_empty() {
+        return Err(Error::MissingCertificate(path.to_path_buf()));
+    }
+    Ok(certificates)
+}
+
+fn private_key(path: &Path) -> Result<PrivateKeyDer<'static>, Error> {
+    rustls_pemfile::private_key(&mut reader(path)?)
+        .map_err(|e| Error::Read(path.to_path_buf(), e))?
+        .ok_or_else(|| Error::MissingPrivateKey(path.to_path_buf()))
+}
+
+impl TlsServer {
+    // The files are read upfront so that a bad configuration fails the startup
+    pub fn new(
+        certificate: &Path,
+        private_key: &Path,
+        client_ca: Option<&Path>,
+    ) -> Result<Self, Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_client_cert_verifier(Arc::new(AnyClientCertificate(provider)))
+            .with_single_cert(certificates(certificate)?, self::private_key(private_key)?)?;
+        let client_ca = match client_ca {
+            Some(path) => Some(ClientCa::new(certificates(path)?)?),
+            None => None,
+        };
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            client_ca,
+        })
+    }
+
+    // Serves the router over TLS, exposing the certificate presented by the
+    // client to the handlers as an `Extension<ClientCertificate>`
+    pub async fn serve(self, router: Router, listener: TcpListener) {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                continue;
+            };
+            let acceptor = self.acceptor.clone();
+            let client_ca = self.client_ca.clone();
+            let router = router.clone();
+            tokio::spawn(async move {
+                let Ok(stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let chain = stream.get_ref().1.peer_certificates().unwrap_or_default();
+                let certificate = chain.first().map(|end_entity| ClientCertificate {
+                    der: end_entity.to_vec(),
+                    trusted: client_ca
+                        .as_ref()
+                        .is_some_and(|client_ca| client_ca.verifies(chain)),
+                });
+                let router = match certificate {
+                    Some(certificate) => router.layer(Extension(certificate)),
+                    None => router,
+                };
+                let service = TowerToHyperService::new(router);
+                let _ = http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await;
+            });
+        }
+    }
+}
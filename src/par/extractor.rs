@@ -0,0 +1,21 @@
+use crate::authorize::extractor::AuthorizeParams;
+use crate::bounded_string::SecureString;
+use crate::data::resource::ResourceForm;
+use axum::extract::FromRequest;
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(ResourceForm))]
+pub struct PushedAuthorizationParams {
+    #[serde(default, deserialize_with = "client_secret")]
+    pub client_secret: Option<SecureString>,
+    #[serde(flatten)]
+    pub params: AuthorizeParams,
+}
+
+fn client_secret<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecureString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `client_secret`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
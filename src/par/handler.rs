@@ -0,0 +1,198 @@
+use super::extractor::PushedAuthorizationParams;
+use crate::authorize::extractor::{self, PUSHED_REQUEST_URI_PREFIX};
+use crate::bounded_string::NonEmptyString;
+use crate::data::authorization_details;
+use crate::data::time::UnixTime;
+use crate::data::ErrorResponse;
+use crate::mtls::ClientCertificate;
+use crate::state::{AppState, PushedRequest};
+use crate::token::handler::{authenticate_client, InvalidParamError};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response, Result};
+use axum::{Extension, Json};
+use serde::Serialize;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc9126#section-2.2
+#[derive(Serialize)]
+pub struct PushedAuthorizationResponse {
+    expires_in: u64,
+    request_uri: NonEmptyString,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidClient(InvalidParamError),
+    #[error(transparent)]
+    InvalidParam(#[from] extractor::Error),
+    #[error(transparent)]
+    AuthorizationDetails(#[from] authorization_details::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let response = match self {
+            // https://datatracker.ietf.org/doc/html/rfc9126#section-2.3
+            Self::InvalidClient(_) => {
+                let response = ErrorResponse::new("invalid_client", &self);
+                return (StatusCode::UNAUTHORIZED, Json(response)).into_response();
+            }
+            Self::InvalidParam(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            Self::AuthorizationDetails(ref e) => {
+                let response = ErrorResponse::new(e.code(), e);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
+        };
+        response.into_response()
+    }
+}
+
+pub async fn par(
+    state: AppState,
+    certificate: Option<Extension<ClientCertificate>>,
+    params: PushedAuthorizationParams,
+) -> Result<impl IntoResponse> {
+    let certificate = certificate
+        .as_ref()
+        .map(|Extension(certificate)| certificate);
+    authenticate_client(
+        &state,
+        &params.params.client_id,
+        params.client_secret,
+        certificate,
+    )
+    .map_err(Error::InvalidClient)?;
+    params.params.validate().map_err(Error::from)?;
+    state
+        .validate_authorization_details(&params.params.authorization_details)
+        .map_err(Error::from)?;
+    let request_uri = format!("{PUSHED_REQUEST_URI_PREFIX}{}", state.gen_secure_string());
+    let request_uri = NonEmptyString::try_from(request_uri).unwrap();
+    let request = PushedRequest {
+        expires_at: UnixTime::now() + state.par_expiration(),
+        params: params.params,
+    };
+    state.set_pushed_request(request_uri.clone(), request);
+    let response = PushedAuthorizationResponse {
+        expires_in: state.par_expiration(),
+        request_uri,
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET, CODE_CHALLENGE, REDIRECT_URI};
+    use axum::http::StatusCode;
+
+    async fn push(state: &crate::state::AppState, client_secret: &str) -> testing::TestResponse {
+        let params = [
+            ("client_id", CLIENT_ID),
+            ("client_secret", client_secret),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", REDIRECT_URI),
+            ("response_mode", "query"),
+            ("response_type", "code"),
+            ("scope", "openid"),
+            ("state", "12345678901234567890"),
+        ];
+        testing::post_form(state, "/par", &params).await
+    }
+
+    fn authorize_uri(client_id: &str, request_uri: &str) -> String {
+        let query =
+            serde_urlencoded::to_string([("client_id", client_id), ("request_uri", request_uri)])
+                .unwrap();
+        format!("/authorize?{query}")
+    }
+
+    #[tokio::test]
+    async fn pushed_request_authorizes() {
+        let state = testing::state(&[]);
+        let response = push(&state, CLIENT_SECRET).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+        assert_eq!(response.json()["expires_in"], 60);
+        let request_uri = response.json()["request_uri"].as_str().unwrap().to_string();
+        assert!(request_uri.starts_with("urn:ietf:params:oauth:request_uri:"));
+        let response = testing::get(&state, &authorize_uri(CLIENT_ID, &request_uri)).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+        assert!(response.redirect_param("code").is_some());
+    }
+
+    #[tokio::test]
+    async fn pushed_request_bound_to_its_client() {
+        let state = testing::state(&[]);
+        let response = push(&state, CLIENT_SECRET).await;
+        let request_uri = response.json()["request_uri"].as_str().unwrap().to_string();
+        let uri = authorize_uri("otherClientIdentifier", &request_uri);
+        let response = testing::get(&state, &uri).await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+    }
+
+    #[tokio::test]
+    async fn pushed_request_required() {
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            (
+                "CLIENTS__WEB__REQUIRE_PUSHED_AUTHORIZATION_REQUESTS",
+                "true",
+            ),
+        ]);
+        let response = testing::authorize(&state, CLIENT_ID).await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+        let response = push(&state, CLIENT_SECRET).await;
+        let request_uri = response.json()["request_uri"].as_str().unwrap().to_string();
+        let response = testing::get(&state, &authorize_uri(CLIENT_ID, &request_uri)).await;
+        assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+    }
+
+    #[tokio::test]
+    async fn client_secret_not_disclosed() {
+        let state = testing::state(&[]);
+        let response = push(&state, "0000000000000000000000000000000000000000").await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert!(!response.body.contains(CLIENT_SECRET));
+    }
+
+    #[tokio::test]
+    async fn mtls_client_authenticated() {
+        let jwks = testing::client_certificate_jwks();
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__JWKS", &jwks),
+            (
+                "CLIENTS__WEB__TOKEN_ENDPOINT_AUTH_METHOD",
+                "self_signed_tls_client_auth",
+            ),
+        ]);
+        let params = [
+            ("client_id", CLIENT_ID),
+            ("code_challenge", CODE_CHALLENGE),
+            ("code_challenge_method", "S256"),
+            ("redirect_uri", REDIRECT_URI),
+            ("response_mode", "query"),
+            ("response_type", "code"),
+            ("scope", "openid"),
+            ("state", "12345678901234567890"),
+        ];
+        let certificate = testing::client_certificate();
+        let response =
+            testing::post_form_with_certificate(&state, "/par", &params, certificate).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+        let response = testing::post_form(&state, "/par", &params).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(response.json()["error"], "invalid_client");
+    }
+}
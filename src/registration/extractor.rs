@@ -0,0 +1,231 @@
+use crate::bounded_string::SecureString;
+use crate::data::ErrorResponse;
+use crate::state::client::{
+    BackchannelTokenDeliveryMode, Client, GrantType, TokenEndpointAuthMethod,
+};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::FromRequest;
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use jsonwebtoken::jwk::JwkSet;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use thiserror::Error;
+
+// Unsupported metadata is ignored
+// https://datatracker.ietf.org/doc/html/rfc7591#section-2
+#[serde_as]
+#[derive(Deserialize, FromRequest, Serialize)]
+#[from_request(via(Json), rejection(Error))]
+pub struct ClientMetadata {
+    // https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#registration
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "http_serde::option::uri"
+    )]
+    pub backchannel_client_notification_endpoint: Option<Uri>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backchannel_token_delivery_mode: Option<BackchannelTokenDeliveryMode>,
+    #[serde(default = "default_grant_types")]
+    pub grant_types: Vec<GrantType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jwks: Option<JwkSet>,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub redirect_uris: Vec<Uri>,
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-2.1.2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_client_auth_subject_dn: Option<String>,
+    #[serde(default)]
+    pub tls_client_certificate_bound_access_tokens: bool,
+    #[serde(default)]
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+}
+
+// The client sends back its identifier and may send back its secret
+// https://datatracker.ietf.org/doc/html/rfc7592#section-2.2
+#[derive(Deserialize, FromRequest)]
+#[from_request(via(Json), rejection(Error))]
+pub struct ClientUpdate {
+    pub client_id: SecureString,
+    #[serde(default)]
+    pub client_secret: Option<SecureString>,
+    #[serde(flatten)]
+    pub metadata: ClientMetadata,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.2
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid client metadata: {}", .0.body_text())]
+    Json(#[from] JsonRejection),
+    #[error("`redirect_uris` must hold absolute URIs with the `authorization_code` and `implicit` grant types")]
+    RedirectUris,
+    #[error("`tls_client_auth_subject_dn` is required with the `tls_client_auth` method")]
+    MissingSubjectDn,
+    #[error("`jwks` is required with the `self_signed_tls_client_auth` method")]
+    MissingJwks,
+    #[error(
+        "`backchannel_token_delivery_mode` is required with the `{}` grant type",
+        GrantType::Ciba
+    )]
+    MissingDeliveryMode,
+    #[error("`backchannel_client_notification_endpoint` is required with the ping delivery mode")]
+    MissingNotificationEndpoint,
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::RedirectUris => "invalid_redirect_uri",
+            _ => "invalid_client_metadata",
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let response = ErrorResponse::new(self.code(), &self);
+        (StatusCode::BAD_REQUEST, Json(response)).into_response()
+    }
+}
+
+fn default_grant_types() -> Vec<GrantType> {
+    vec![GrantType::AuthorizationCode]
+}
+
+impl ClientMetadata {
+    pub fn validate(&self) -> Result<(), Error> {
+        // https://datatracker.ietf.org/doc/html/rfc6749#section-3.1.2
+        let redirection = self.grant_types.iter().any(|grant_type| {
+            matches!(
+                grant_type,
+                GrantType::AuthorizationCode | GrantType::Implicit
+            )
+        });
+        let absolute = |uri: &Uri| uri.scheme().is_some() && uri.authority().is_some();
+        if redirection
+            && (self.redirect_uris.is_empty() || !self.redirect_uris.iter().all(absolute))
+        {
+            return Err(Error::RedirectUris);
+        }
+        match self.token_endpoint_auth_method {
+            TokenEndpointAuthMethod::TlsClientAuth if self.tls_client_auth_subject_dn.is_none() => {
+                return Err(Error::MissingSubjectDn)
+            }
+            TokenEndpointAuthMethod::SelfSignedTlsClientAuth if self.jwks.is_none() => {
+                return Err(Error::MissingJwks)
+            }
+            _ => {}
+        }
+        if self.grant_types.contains(&GrantType::Ciba) {
+            match self.backchannel_token_delivery_mode {
+                None => return Err(Error::MissingDeliveryMode),
+                Some(BackchannelTokenDeliveryMode::Ping)
+                    if self.backchannel_client_notification_endpoint.is_none() =>
+                {
+                    return Err(Error::MissingNotificationEndpoint)
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Only clients authenticating with a shared secret are issued one
+    pub fn into_client(
+        self,
+        client_id: SecureString,
+        client_secret: Option<SecureString>,
+    ) -> Client {
+        let client_secret = match self.token_endpoint_auth_method {
+            TokenEndpointAuthMethod::ClientSecretPost => client_secret,
+            _ => None,
+        };
+        Client {
+            backchannel_client_notification_endpoint: self.backchannel_client_notification_endpoint,
+            backchannel_token_delivery_mode: self.backchannel_token_delivery_mode,
+            client_secret,
+            password_grant: self.grant_types.contains(&GrantType::Password),
+            grant_types: self.grant_types,
+            jwks: self.jwks,
+            redirect_uris: self.redirect_uris,
+            tls_client_auth_subject_dn: self.tls_client_auth_subject_dn,
+            tls_client_certificate_bound_access_tokens: self
+                .tls_client_certificate_bound_access_tokens,
+            token_endpoint_auth_method: self.token_endpoint_auth_method,
+            ..Client::unregistered(client_id)
+        }
+    }
+}
+
+impl From<&Client> for ClientMetadata {
+    fn from(client: &Client) -> Self {
+        Self {
+            backchannel_client_notification_endpoint: client
+                .backchannel_client_notification_endpoint
+                .clone(),
+            backchannel_token_delivery_mode: client.backchannel_token_delivery_mode,
+            grant_types: client.grant_types.clone(),
+            jwks: client.jwks.clone(),
+            redirect_uris: client.redirect_uris.clone(),
+            tls_client_auth_subject_dn: client.tls_client_auth_subject_dn.clone(),
+            tls_client_certificate_bound_access_tokens: client
+                .tls_client_certificate_bound_access_tokens,
+            token_endpoint_auth_method: client.token_endpoint_auth_method,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(value: serde_json::Value) -> ClientMetadata {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn redirection_grants_require_absolute_redirect_uris() {
+        let default = metadata(json!({}));
+        assert!(matches!(default.validate(), Err(Error::RedirectUris)));
+        let relative = metadata(json!({"redirect_uris": ["/callback"]}));
+        assert!(matches!(relative.validate(), Err(Error::RedirectUris)));
+        let absolute = metadata(json!({"redirect_uris": ["https://app.example.com/callback"]}));
+        assert!(absolute.validate().is_ok());
+        let password = metadata(json!({"grant_types": ["password"]}));
+        assert!(password.validate().is_ok());
+    }
+
+    #[test]
+    fn ping_mode_requires_notification_endpoint() {
+        let ciba = metadata(json!({
+            "grant_types": ["urn:openid:params:grant-type:ciba"],
+            "backchannel_token_delivery_mode": "ping",
+        }));
+        assert!(matches!(
+            ciba.validate(),
+            Err(Error::MissingNotificationEndpoint)
+        ));
+    }
+
+    #[test]
+    fn only_secret_clients_get_a_secret() {
+        let client_id: SecureString = "mmMoPLrw_bzrH2R5sZfzqI3Q2Ak".try_into().unwrap();
+        let secret: SecureString = "L+cqfFYoFThziwficu6lEZQQlzo=".try_into().unwrap();
+        let client = metadata(json!({
+            "grant_types": ["password"],
+            "token_endpoint_auth_method": "tls_client_auth",
+            "tls_client_auth_subject_dn": "CN=client.example.com",
+        }))
+        .into_client(client_id.clone(), Some(secret.clone()));
+        assert!(client.client_secret.is_none());
+        assert!(client.password_grant);
+        let client = metadata(json!({"grant_types": ["password"]}))
+            .into_client(client_id, Some(secret.clone()));
+        assert_eq!(client.client_secret, Some(secret));
+    }
+}
@@ -0,0 +1,353 @@
+use super::extractor::{self, ClientMetadata, ClientUpdate};
+use crate::bounded_string::SecureString;
+use crate::data::time::UnixTime;
+use crate::data::{quoted_string, ErrorResponse};
+use crate::state::client::{Client, Registration};
+use crate::state::AppState;
+use axum::extract::Path;
+use axum::http::header::WWW_AUTHENTICATE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response, Result};
+use axum::Json;
+use axum_extra::headers::authorization::Bearer;
+use axum_extra::headers::Authorization;
+use axum_extra::TypedHeader;
+use serde::Serialize;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.1
+// https://datatracker.ietf.org/doc/html/rfc7592#section-3
+#[derive(Serialize)]
+pub struct ClientInformation {
+    client_id: SecureString,
+    client_id_issued_at: UnixTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret: Option<SecureString>,
+    // The secret never expires
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_secret_expires_at: Option<u64>,
+    #[serde(flatten)]
+    metadata: ClientMetadata,
+    registration_access_token: SecureString,
+    registration_client_uri: String,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    InvalidMetadata(#[from] extractor::Error),
+    #[error("the registration access token is missing or invalid")]
+    InvalidToken,
+    #[error("`client_id` does not match: expected `{0}` got `{1}`")]
+    ClientId(SecureString, SecureString),
+    #[error("`client_secret` does not match the issued one")]
+    ClientSecret,
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        match self {
+            Self::InvalidMetadata(e) => e.into_response(),
+            // https://datatracker.ietf.org/doc/html/rfc6750#section-3
+            Self::InvalidToken => {
+                let challenge = format!(
+                    r#"Bearer error="invalid_token", error_description={}"#,
+                    quoted_string(&self.to_string())
+                );
+                let header = [(WWW_AUTHENTICATE, challenge)];
+                (StatusCode::UNAUTHORIZED, header, self.to_string()).into_response()
+            }
+            // https://datatracker.ietf.org/doc/html/rfc7592#section-2.2
+            Self::ClientId(..) | Self::ClientSecret => {
+                let response = ErrorResponse::new("invalid_client_metadata", &self);
+                (StatusCode::BAD_REQUEST, Json(response)).into_response()
+            }
+        }
+    }
+}
+
+fn information(
+    state: &AppState,
+    client: &Client,
+    registration: &Registration,
+) -> ClientInformation {
+    ClientInformation {
+        client_id: client.client_id.clone(),
+        client_id_issued_at: registration.client_id_issued_at,
+        client_secret: client.client_secret.clone(),
+        client_secret_expires_at: client.client_secret.as_ref().map(|_| 0),
+        metadata: client.into(),
+        registration_access_token: registration.access_token.clone(),
+        registration_client_uri: state.endpoint(&format!("/register/{}", client.client_id)),
+    }
+}
+
+// Only dynamically registered clients can be managed, with their registration access token
+// https://datatracker.ietf.org/doc/html/rfc7592#section-2
+fn registered_client(
+    state: &AppState,
+    client_id: String,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<(Client, Registration), Error> {
+    let client_id = SecureString::try_from(client_id).map_err(|_| Error::InvalidToken)?;
+    let registration = state
+        .registration(&client_id)
+        .filter(|registration| {
+            authorization.is_some_and(|TypedHeader(Authorization(bearer))| {
+                bearer.token() == registration.access_token.to_string()
+            })
+        })
+        .ok_or(Error::InvalidToken)?;
+    Ok((state.client(&client_id), registration))
+}
+
+pub async fn register(state: AppState, metadata: ClientMetadata) -> Result<impl IntoResponse> {
+    metadata.validate().map_err(Error::from)?;
+    let client = metadata.into_client(state.gen_client_id(), Some(state.gen_secure_string()));
+    let registration = Registration {
+        access_token: state.gen_secure_string(),
+        client_id_issued_at: UnixTime::now(),
+    };
+    let response = information(&state, &client, &registration);
+    state.register_client(client, registration);
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7592#section-2.1
+pub async fn read_client(
+    state: AppState,
+    Path(client_id): Path<String>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<Json<ClientInformation>> {
+    let (client, registration) = registered_client(&state, client_id, authorization)?;
+    Ok(Json(information(&state, &client, &registration)))
+}
+
+// The metadata is replaced as a whole, keeping the credentials of the client
+// https://datatracker.ietf.org/doc/html/rfc7592#section-2.2
+pub async fn update_client(
+    state: AppState,
+    Path(client_id): Path<String>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+    update: ClientUpdate,
+) -> Result<Json<ClientInformation>> {
+    let (client, registration) = registered_client(&state, client_id, authorization)?;
+    if update.client_id != client.client_id {
+        return Err(Error::ClientId(client.client_id.clone(), update.client_id).into());
+    }
+    if update
+        .client_secret
+        .is_some_and(|secret| Some(secret) != client.client_secret)
+    {
+        return Err(Error::ClientSecret.into());
+    }
+    update.metadata.validate().map_err(Error::from)?;
+    let client_secret = client
+        .client_secret
+        .unwrap_or_else(|| state.gen_secure_string());
+    let client = update
+        .metadata
+        .into_client(client.client_id, Some(client_secret));
+    let response = information(&state, &client, &registration);
+    state.register_client(client, registration);
+    Ok(Json(response))
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7592#section-2.3
+pub async fn delete_client(
+    state: AppState,
+    Path(client_id): Path<String>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<StatusCode> {
+    let (client, _) = registered_client(&state, client_id, authorization)?;
+    state.unregister_client(&client.client_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::AppState;
+    use crate::testing::{self, CLIENT_SECRET, CODE_VERIFIER, REDIRECT_URI};
+    use axum::body::Body;
+    use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+    use axum::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    async fn send(
+        state: &AppState,
+        method: Method,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<Value>,
+    ) -> testing::TestResponse {
+        let mut request = testing::request(method, uri);
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let body = match body {
+            Some(body) => {
+                request = request.header(CONTENT_TYPE, "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        testing::send(state, request.body(body).unwrap()).await
+    }
+
+    async fn register(state: &AppState) -> Value {
+        let metadata = json!({
+            "grant_types": [
+                "authorization_code",
+                "urn:ietf:params:oauth:grant-type:device_code",
+            ],
+            "redirect_uris": [REDIRECT_URI],
+        });
+        let response = send(state, Method::POST, "/register", None, Some(metadata)).await;
+        assert_eq!(response.status, StatusCode::CREATED, "{}", response.body);
+        response.json()
+    }
+
+    // The path of the `registration_client_uri` and the registration access token
+    fn management(information: &Value) -> (String, String) {
+        let uri = information["registration_client_uri"].as_str().unwrap();
+        let path = uri.trim_start_matches("https://login.helloprima.com");
+        let token = information["registration_access_token"].as_str().unwrap();
+        (path.to_string(), token.to_string())
+    }
+
+    #[tokio::test]
+    async fn registration_client_uri_at_the_issuer() {
+        let state = testing::state(&[]);
+        let information = register(&state).await;
+        let client_id = information["client_id"].as_str().unwrap();
+        assert_eq!(
+            information["registration_client_uri"],
+            format!("https://login.helloprima.com/register/{client_id}")
+        );
+    }
+
+    #[tokio::test]
+    async fn read() {
+        let state = testing::state(&[]);
+        let information = register(&state).await;
+        let (path, token) = management(&information);
+        let response = send(&state, Method::GET, &path, Some(&token), None).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.json(), information);
+    }
+
+    #[tokio::test]
+    async fn update() {
+        let state = testing::state(&[]);
+        let information = register(&state).await;
+        let (path, token) = management(&information);
+        let update = json!({
+            "client_id": information["client_id"],
+            "redirect_uris": ["http://localhost:3000/callback"],
+        });
+        let response = send(&state, Method::PUT, &path, Some(&token), Some(update)).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let updated = response.json();
+        assert_eq!(
+            updated["redirect_uris"],
+            json!(["http://localhost:3000/callback"])
+        );
+        assert_eq!(updated["client_secret"], information["client_secret"]);
+    }
+
+    #[tokio::test]
+    async fn update_of_another_client_id() {
+        let state = testing::state(&[]);
+        let information = register(&state).await;
+        let (path, token) = management(&information);
+        let update = json!({
+            "client_id": "otherClientIdentifier",
+            "redirect_uris": [REDIRECT_URI],
+        });
+        let response = send(&state, Method::PUT, &path, Some(&token), Some(update)).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_client_metadata");
+    }
+
+    #[tokio::test]
+    async fn registration_access_token_required() {
+        let state = testing::state(&[]);
+        let information = register(&state).await;
+        let other = register(&state).await;
+        let (path, _) = management(&information);
+        let (_, other_token) = management(&other);
+        for token in [
+            None,
+            Some("0000000000000000000000000000"),
+            Some(&other_token),
+        ] {
+            let response = send(&state, Method::GET, &path, token, None).await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+            let challenge = response.header(WWW_AUTHENTICATE.as_str());
+            assert!(challenge.starts_with(r#"Bearer error="invalid_token""#));
+        }
+    }
+
+    #[tokio::test]
+    async fn delete() {
+        let state = testing::state(&[]);
+        let information = register(&state).await;
+        let client_id = information["client_id"].as_str().unwrap();
+        let client_secret = information["client_secret"].as_str().unwrap();
+        let mut codes = Vec::new();
+        for _ in 0..2 {
+            codes.push(testing::authorization_code(&state, client_id).await);
+        }
+        let device_params = [
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("scope", "openid"),
+        ];
+        let response = testing::post_form(&state, "/device_authorization", &device_params).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let device_authorization = response.json();
+        let user_code = device_authorization["user_code"].as_str().unwrap();
+        let params = [("user_code", user_code), ("action", "approve")];
+        let response = testing::post_form(&state, "/device", &params).await;
+        assert_eq!(response.status, StatusCode::OK);
+        let (path, token) = management(&information);
+        let response = send(&state, Method::DELETE, &path, Some(&token), None).await;
+        assert_eq!(response.status, StatusCode::NO_CONTENT);
+        let response = send(&state, Method::GET, &path, Some(&token), None).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+        // The deleted client does not fall back to an unregistered client
+        for (client_secret, code) in [client_secret, CLIENT_SECRET].iter().zip(&codes) {
+            let params = [
+                ("grant_type", "authorization_code"),
+                ("client_id", client_id),
+                ("client_secret", client_secret),
+                ("code", code),
+                ("code_verifier", CODE_VERIFIER),
+                ("redirect_uri", REDIRECT_URI),
+            ];
+            let response = testing::post_form(&state, "/token", &params).await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+            assert_eq!(response.json()["error"], "invalid_client");
+        }
+        let response = testing::authorize(&state, client_id).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        // Nor for the device grant, with or without its former secret
+        let device_code = device_authorization["device_code"].as_str().unwrap();
+        for client_secret in [Some(client_secret), Some(CLIENT_SECRET), None] {
+            let mut params = vec![
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("client_id", client_id),
+                ("device_code", device_code),
+            ];
+            params.extend(client_secret.map(|client_secret| ("client_secret", client_secret)));
+            let response = testing::post_form(&state, "/token", &params).await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+            assert_eq!(response.json()["error"], "invalid_client");
+            let mut params = vec![("client_id", client_id), ("scope", "openid")];
+            params.extend(client_secret.map(|client_secret| ("client_secret", client_secret)));
+            let response = testing::post_form(&state, "/device_authorization", &params).await;
+            assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+            assert_eq!(response.json()["error"], "invalid_client");
+        }
+    }
+}
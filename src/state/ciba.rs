@@ -0,0 +1,36 @@
+use super::device::PendingGrant;
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::data::Scope;
+use serde::Serialize;
+use std::collections::HashSet;
+
+// An authentication request waiting for the user to approve it on their own device
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#auth_request
+pub struct BackchannelRequest {
+    pub binding_message: Option<NonEmptyString>,
+    pub client_notification_token: Option<NonEmptyString>,
+    pub login_hint: NonEmptyString,
+    pub pending: PendingGrant,
+}
+
+// What the user is shown before approving or denying the request
+#[derive(Serialize)]
+pub struct PendingRequest {
+    pub auth_req_id: SecureString,
+    pub binding_message: Option<NonEmptyString>,
+    pub client_id: SecureString,
+    pub login_hint: NonEmptyString,
+    pub scope: HashSet<Scope>,
+}
+
+impl PendingRequest {
+    pub fn new(auth_req_id: &SecureString, request: &BackchannelRequest) -> Self {
+        Self {
+            auth_req_id: auth_req_id.clone(),
+            binding_message: request.binding_message.clone(),
+            client_id: request.pending.grant.client_id.clone(),
+            login_hint: request.login_hint.clone(),
+            scope: request.pending.grant.scope.clone(),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::bounded_string::SecureString;
+use crate::crypto::jwe::{self, ContentEncryptionAlgorithm, KeyManagementAlgorithm};
+use crate::data::access_token::{AccessTokenFormat, AccessTokenProfile};
+use crate::data::time::UnixTime;
+use axum::http::Uri;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::Algorithm;
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::formats::SpaceSeparator;
+use serde_with::json::JsonString;
+use serde_with::{serde_as, StringWithSeparator};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+// https://datatracker.ietf.org/doc/html/rfc7591#section-2
+#[serde_as]
+#[derive(Clone, Deserialize)]
+pub struct Client {
+    // The claim names of the access tokens issued to the client
+    #[serde(default)]
+    pub access_token_format: Option<AccessTokenFormat>,
+    #[serde(default)]
+    pub access_token_profile: Option<AccessTokenProfile>,
+    // https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#registration
+    #[serde(default, with = "http_serde::option::uri")]
+    pub backchannel_client_notification_endpoint: Option<Uri>,
+    // https://openid.net/specs/openid-connect-backchannel-1_0.html#BCRegistration
+    #[serde(default, with = "http_serde::option::uri")]
+    pub backchannel_logout_uri: Option<Uri>,
+    // https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#registration
+    #[serde(default)]
+    pub backchannel_token_delivery_mode: Option<BackchannelTokenDeliveryMode>,
+    pub client_id: SecureString,
+    #[serde(default)]
+    pub client_secret: Option<SecureString>,
+    // https://openid.net/specs/openid-connect-frontchannel-1_0.html#RPLogout
+    #[serde(default, with = "http_serde::option::uri")]
+    pub frontchannel_logout_uri: Option<Uri>,
+    #[serde(default)]
+    pub frontchannel_logout_session_required: bool,
+    // Any grant type when empty
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, GrantType>")]
+    #[serde(default)]
+    pub grant_types: Vec<GrantType>,
+    // https://openid.net/specs/openid-connect-registration-1_0.html#ClientMetadata
+    #[serde(default)]
+    pub id_token_encrypted_response_alg: Option<KeyManagementAlgorithm>,
+    #[serde(default)]
+    pub id_token_encrypted_response_enc: ContentEncryptionAlgorithm,
+    #[serde_as(as = "Option<JsonString>")]
+    #[serde(default)]
+    pub jwks: Option<JwkSet>,
+    // https://datatracker.ietf.org/doc/html/rfc6749#section-4.3
+    #[serde(default)]
+    pub password_grant: bool,
+    // Any post logout redirection URI when empty
+    // https://openid.net/specs/openid-connect-rpinitiated-1_0.html#ClientMetadata
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, Uri>")]
+    #[serde(default)]
+    pub post_logout_redirect_uris: Vec<Uri>,
+    // Any redirection URI when empty
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, Uri>")]
+    #[serde(default)]
+    pub redirect_uris: Vec<Uri>,
+    #[serde(default)]
+    pub request_object_signing_alg: Option<Algorithm>,
+    // https://openid.net/specs/openid-connect-registration-1_0.html#ClientMetadata
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, Uri>")]
+    #[serde(default)]
+    pub request_uris: Vec<Uri>,
+    // https://datatracker.ietf.org/doc/html/rfc9126#section-6
+    #[serde(default)]
+    pub require_pushed_authorization_requests: bool,
+    // https://datatracker.ietf.org/doc/html/rfc8693#section-5
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, Uri>")]
+    #[serde(default)]
+    pub token_exchange_audiences: Vec<Uri>,
+    #[serde(default)]
+    pub token_exchange_impersonation: bool,
+    // https://datatracker.ietf.org/doc/html/rfc7591#section-2
+    #[serde(default)]
+    pub token_endpoint_auth_method: TokenEndpointAuthMethod,
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-2.1.2
+    #[serde(default)]
+    pub tls_client_auth_subject_dn: Option<String>,
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-3.4
+    #[serde(default)]
+    pub tls_client_certificate_bound_access_tokens: bool,
+    #[serde(default)]
+    pub userinfo_encrypted_response_alg: Option<KeyManagementAlgorithm>,
+    #[serde(default)]
+    pub userinfo_encrypted_response_enc: ContentEncryptionAlgorithm,
+    #[serde(default, deserialize_with = "userinfo_signed_response_alg")]
+    pub userinfo_signed_response_alg: Option<Algorithm>,
+}
+
+// The push mode is not supported
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#rfc.section.5
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackchannelTokenDeliveryMode {
+    Poll,
+    Ping,
+}
+
+// The credentials of a dynamically registered client to manage its registration
+// https://datatracker.ietf.org/doc/html/rfc7592#section-3
+#[derive(Clone)]
+pub struct Registration {
+    pub access_token: SecureString,
+    pub client_id_issued_at: UnixTime,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc7591#section-2
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum GrantType {
+    #[serde(rename = "authorization_code")]
+    AuthorizationCode,
+    #[serde(rename = "implicit")]
+    Implicit,
+    #[serde(rename = "password")]
+    Password,
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode,
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange,
+    #[serde(rename = "urn:openid:params:grant-type:ciba")]
+    Ciba,
+}
+
+impl FromStr for GrantType {
+    type Err = serde::de::value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(s.into_deserializer())
+    }
+}
+
+impl Display for GrantType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.serialize(f)
+    }
+}
+
+// https://www.iana.org/assignments/oauth-parameters/oauth-parameters.xhtml#token-endpoint-auth-method
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenEndpointAuthMethod {
+    #[default]
+    ClientSecretPost,
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-2.1
+    TlsClientAuth,
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-2.2
+    SelfSignedTlsClientAuth,
+}
+
+impl Client {
+    // https://datatracker.ietf.org/doc/html/rfc7591#section-2.1
+    pub fn allows_grant_type(&self, grant_type: GrantType) -> bool {
+        self.grant_types.is_empty() || self.grant_types.contains(&grant_type)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc6749#section-3.1.2.2
+    pub fn allows_redirect_uri(&self, redirect_uri: &Uri) -> bool {
+        self.redirect_uris.is_empty() || self.redirect_uris.contains(redirect_uri)
+    }
+
+    pub fn allows_post_logout_redirect_uri(&self, redirect_uri: &Uri) -> bool {
+        self.post_logout_redirect_uris.is_empty()
+            || self.post_logout_redirect_uris.contains(redirect_uri)
+    }
+
+    pub fn encrypt(
+        &self,
+        plaintext: &[u8],
+        alg: KeyManagementAlgorithm,
+        enc: ContentEncryptionAlgorithm,
+        cty: Option<&'static str>,
+    ) -> Result<String, jwe::Error> {
+        let jwks = self.jwks.as_ref().ok_or(jwe::Error::MissingKey(alg))?;
+        jwe::encrypt(plaintext, jwks, alg, enc, cty)
+    }
+
+    // Unregistered clients are accepted and authenticated with the global secret
+    pub fn unregistered(client_id: SecureString) -> Self {
+        Self {
+            access_token_format: None,
+            access_token_profile: None,
+            backchannel_client_notification_endpoint: None,
+            backchannel_logout_uri: None,
+            backchannel_token_delivery_mode: None,
+            client_id,
+            client_secret: None,
+            frontchannel_logout_uri: None,
+            frontchannel_logout_session_required: false,
+            grant_types: Vec::new(),
+            id_token_encrypted_response_alg: None,
+            id_token_encrypted_response_enc: Default::default(),
+            jwks: None,
+            password_grant: false,
+            post_logout_redirect_uris: Vec::new(),
+            redirect_uris: Vec::new(),
+            request_object_signing_alg: None,
+            request_uris: Vec::new(),
+            require_pushed_authorization_requests: false,
+            token_exchange_audiences: Vec::new(),
+            token_exchange_impersonation: false,
+            token_endpoint_auth_method: Default::default(),
+            tls_client_auth_subject_dn: None,
+            tls_client_certificate_bound_access_tokens: false,
+            userinfo_encrypted_response_alg: None,
+            userinfo_encrypted_response_enc: Default::default(),
+            userinfo_signed_response_alg: None,
+        }
+    }
+}
+
+// The userinfo responses are signed with the shared secret of the signing keys
+fn userinfo_signed_response_alg<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<Algorithm>, D::Error> {
+    let alg = Option::<Algorithm>::deserialize(d)
+        .map_err(|e| format!("error while parsing field `userinfo_signed_response_alg`: {e}"))
+        .map_err(serde::de::Error::custom)?;
+    match alg {
+        None | Some(Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) => Ok(alg),
+        Some(alg) => Err(serde::de::Error::custom(format!(
+            "error while parsing field `userinfo_signed_response_alg`: unsupported algorithm `{alg:?}`"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn client(userinfo_signed_response_alg: &str) -> Result<Client, serde_json::Error> {
+        serde_json::from_value(json!({
+            "client_id": "0jrabyQWm4B9zVJPbotY",
+            "userinfo_signed_response_alg": userinfo_signed_response_alg,
+        }))
+    }
+
+    #[test]
+    fn userinfo_signed_with_hmac() {
+        let client = client("HS384").unwrap();
+        assert_eq!(client.userinfo_signed_response_alg, Some(Algorithm::HS384));
+    }
+
+    #[test]
+    fn userinfo_signed_with_asymmetric_key_rejected() {
+        for alg in ["RS256", "ES256", "EdDSA"] {
+            assert!(client(alg).is_err(), "{alg}");
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::data::Scope;
+use std::collections::HashSet;
+
+// The scopes a user granted to a client, out of the ones the client requested
+pub struct Consent {
+    pub granted: HashSet<Scope>,
+    pub requested: HashSet<Scope>,
+}
+
+impl Consent {
+    // A remembered consent only applies to the scopes the user already reviewed
+    pub fn apply(&self, requested: &HashSet<Scope>) -> Option<HashSet<Scope>> {
+        if requested.is_subset(&self.requested) {
+            Some(requested.intersection(&self.granted).cloned().collect())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn consent() -> Consent {
+        Consent {
+            granted: [Scope::Openid, Scope::Email].into(),
+            requested: [Scope::Openid, Scope::Email, Scope::Profile].into(),
+        }
+    }
+
+    #[test]
+    fn reviewed_scopes() {
+        let result = consent().apply(&[Scope::Openid, Scope::Profile].into());
+        assert_eq!(result, Some([Scope::Openid].into()))
+    }
+
+    #[test]
+    fn new_scope() {
+        let result = consent().apply(&[Scope::Openid, Scope::Phone].into());
+        assert!(result.is_none())
+    }
+}
@@ -0,0 +1,175 @@
+use super::Grant;
+use crate::data::time::UnixTime;
+use derive_more::derive::Display;
+use rand::{CryptoRng, Rng};
+use serde::Deserialize;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc8628#section-6.1
+const USER_CODE_CHARSET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+const SLOW_DOWN_INCREMENT: u64 = 5;
+
+#[derive(Clone, Debug, Deserialize, Display, PartialEq)]
+#[display("{}-{}", &_0[..USER_CODE_LENGTH / 2], &_0[USER_CODE_LENGTH / 2..])]
+#[serde(try_from = "String")]
+pub struct UserCode(String);
+
+#[derive(Debug, Error)]
+#[error("user code must be {USER_CODE_LENGTH} characters long: found `{0}`")]
+pub struct UserCodeError(String);
+
+impl UserCode {
+    pub fn generate<R: Rng + CryptoRng>(rng: &mut R) -> Self {
+        let code = (0..USER_CODE_LENGTH)
+            .map(|_| USER_CODE_CHARSET[rng.gen_range(0..USER_CODE_CHARSET.len())] as char)
+            .collect();
+        Self(code)
+    }
+}
+
+impl TryFrom<String> for UserCode {
+    type Error = UserCodeError;
+
+    // The user code is case insensitive and the separator is only for readability
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let code: String = value
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if code.len() == USER_CODE_LENGTH {
+            Ok(Self(code))
+        } else {
+            Err(UserCodeError(value))
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub enum DeviceStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+// A grant waiting for the user to approve it on another device while the client polls
+pub struct PendingGrant {
+    expires_at: UnixTime,
+    pub grant: Grant,
+    interval: u64,
+    last_poll: Option<UnixTime>,
+    status: DeviceStatus,
+}
+
+pub struct DeviceSession {
+    pub pending: PendingGrant,
+    pub user_code: UserCode,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8628#section-3.5
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#token_error_response
+#[derive(Debug, Error)]
+pub enum PollError {
+    #[error("the authorization request is still pending")]
+    AuthorizationPending,
+    #[error("polling too frequently, the interval has been increased")]
+    SlowDown,
+    #[error("the device code or the authentication request has expired")]
+    ExpiredToken,
+    #[error("the authorization request has been denied")]
+    AccessDenied,
+    #[error("no pending authorization associated with the code")]
+    InvalidGrant,
+}
+
+impl PollError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AuthorizationPending => "authorization_pending",
+            Self::SlowDown => "slow_down",
+            Self::ExpiredToken => "expired_token",
+            Self::AccessDenied => "access_denied",
+            Self::InvalidGrant => "invalid_grant",
+        }
+    }
+}
+
+impl DeviceSession {
+    pub fn new(grant: Grant, user_code: UserCode, expiration: u64, interval: u64) -> Self {
+        Self {
+            pending: PendingGrant::new(grant, expiration, interval),
+            user_code,
+        }
+    }
+}
+
+impl PendingGrant {
+    pub fn new(grant: Grant, expiration: u64, interval: u64) -> Self {
+        Self {
+            expires_at: UnixTime::now() + expiration,
+            grant,
+            interval,
+            last_poll: None,
+            status: DeviceStatus::Pending,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status == DeviceStatus::Pending && !self.is_expired()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.expired()
+    }
+
+    // The user authenticates on the verification page or with the backchannel action
+    pub fn resolve(&mut self, approved: bool) {
+        self.grant.auth_time = UnixTime::now();
+        self.status = if approved {
+            DeviceStatus::Approved
+        } else {
+            DeviceStatus::Denied
+        };
+    }
+
+    pub fn poll(&mut self) -> Result<Grant, PollError> {
+        if self.is_expired() {
+            return Err(PollError::ExpiredToken);
+        }
+        let now = UnixTime::now();
+        let last_poll = self.last_poll.replace(now);
+        match self.status {
+            DeviceStatus::Approved => Ok(self.grant.clone()),
+            DeviceStatus::Denied => Err(PollError::AccessDenied),
+            DeviceStatus::Pending if last_poll.is_some_and(|t| now < t + self.interval) => {
+                self.interval += SLOW_DOWN_INCREMENT;
+                Err(PollError::SlowDown)
+            }
+            DeviceStatus::Pending => Err(PollError::AuthorizationPending),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn user_code_normalized() {
+        let result = UserCode::try_from("wdjb-mjht".to_string());
+        assert_eq!(result.unwrap(), UserCode("WDJBMJHT".into()))
+    }
+
+    #[test]
+    fn user_code_display() {
+        let code = UserCode("WDJBMJHT".into());
+        assert_eq!(code.to_string(), "WDJB-MJHT")
+    }
+
+    #[test]
+    fn user_code_too_short() {
+        let result = UserCode::try_from("WDJB".to_string());
+        assert!(result.is_err())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::data::time::UnixTime;
+use crate::data::Secret;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::{CryptoRng, Rng};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+const KEY_LENGTH: usize = 32;
+const KID_LENGTH: usize = 8;
+
+// Keys are published ahead of their use as `next`, sign as `active` and keep
+// verifying the tokens already issued as `previous` until these tokens expire
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStatus {
+    Next,
+    Active,
+    Previous,
+}
+
+#[derive(Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub retires_at: Option<UnixTime>,
+    pub secret: Secret,
+    pub status: KeyStatus,
+}
+
+impl SigningKey {
+    fn new(secret: Secret, status: KeyStatus) -> Self {
+        let kid = URL_SAFE_NO_PAD.encode(&Sha256::digest(secret.as_ref())[..KID_LENGTH]);
+        Self {
+            kid,
+            retires_at: None,
+            secret,
+            status,
+        }
+    }
+
+    fn is_retired(&self) -> bool {
+        self.retires_at
+            .is_some_and(|retires_at| retires_at.expired())
+    }
+
+    fn generate<R: Rng + CryptoRng>(rng: &mut R, status: KeyStatus) -> Self {
+        let secret = rng.gen::<[u8; KEY_LENGTH]>().to_vec();
+        Self::new(secret.into(), status)
+    }
+}
+
+pub struct KeySet(Vec<SigningKey>);
+
+impl KeySet {
+    pub fn new<R: Rng + CryptoRng>(secret: Secret, rng: &mut R) -> Self {
+        Self(vec![
+            SigningKey::new(secret, KeyStatus::Active),
+            SigningKey::generate(rng, KeyStatus::Next),
+        ])
+    }
+
+    pub fn active(&self) -> &SigningKey {
+        self.0
+            .iter()
+            .find(|key| key.status == KeyStatus::Active)
+            .unwrap()
+    }
+
+    pub fn find(&self, kid: &str) -> Option<&SigningKey> {
+        self.keys().find(|key| key.kid == kid)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &SigningKey> {
+        self.0.iter().filter(|key| !key.is_retired())
+    }
+
+    // The previous active key is retired once the tokens it signed have expired
+    pub fn rotate<R: Rng + CryptoRng>(&mut self, rng: &mut R, lifetime: u64) {
+        let retires_at = UnixTime::now() + lifetime;
+        self.0.retain(|key| !key.is_retired());
+        for key in self.0.iter_mut() {
+            match key.status {
+                KeyStatus::Next => key.status = KeyStatus::Active,
+                KeyStatus::Active => {
+                    key.status = KeyStatus::Previous;
+                    key.retires_at = Some(retires_at);
+                }
+                KeyStatus::Previous => {}
+            }
+        }
+        self.0.push(SigningKey::generate(rng, KeyStatus::Next));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn key_set() -> (KeySet, StdRng) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let keys = KeySet::new(b"secret".to_vec().into(), &mut rng);
+        (keys, rng)
+    }
+
+    #[test]
+    fn rotate() {
+        let (mut keys, mut rng) = key_set();
+        let first = keys.active().kid.clone();
+        keys.rotate(&mut rng, 60);
+        assert_ne!(keys.active().kid, first);
+        assert_eq!(keys.find(&first).unwrap().status, KeyStatus::Previous)
+    }
+
+    #[test]
+    fn previous_keys_verify_until_tokens_expire() {
+        let (mut keys, mut rng) = key_set();
+        let first = keys.active().kid.clone();
+        keys.rotate(&mut rng, 60);
+        keys.rotate(&mut rng, 60);
+        assert_eq!(keys.find(&first).unwrap().status, KeyStatus::Previous)
+    }
+
+    #[test]
+    fn retired_keys_do_not_verify() {
+        let (mut keys, mut rng) = key_set();
+        let first = keys.active().kid.clone();
+        keys.rotate(&mut rng, 0);
+        assert!(keys.find(&first).is_none());
+        keys.rotate(&mut rng, 0);
+        assert_eq!(keys.0.len(), 3)
+    }
+}
@@ -1,14 +1,34 @@
+pub mod ciba;
+pub mod client;
+pub mod consent;
+pub mod device;
+pub mod keys;
+pub mod resource;
+pub mod session;
+
+use crate::authentication::totp::TotpSecret;
+use crate::authorize::extractor::AuthorizeParams;
 use crate::bounded_string::{NonEmptyString, SecureString};
 use crate::config::Configuration;
-use crate::crypto::strong_random_bytes;
+use crate::crypto::{hash, strong_random_bytes, url_safe_random_bytes};
+use crate::data::access_token::{AccessToken, AccessTokenFormat, AccessTokenProfile};
+use crate::data::authorization_details::{self, AuthorizationDetail, AuthorizationDetailsType};
 use crate::data::pkce::CodeChallenge;
-use crate::data::{Scope, Secret};
+use crate::data::time::UnixTime;
+use crate::data::Scope;
 use axum::extract::FromRequestParts;
 use axum::http::Uri;
+use ciba::{BackchannelRequest, PendingRequest};
+use client::{Client, Registration};
+use consent::Consent;
 use derive_more::derive::AsRef;
+use device::{DeviceSession, PollError, UserCode};
+use keys::{KeySet, SigningKey};
 use rand::rngs::StdRng;
 use rand::SeedableRng;
+use resource::{Audience, ResourceServer};
 use serde::{Deserialize, Serialize};
+use session::{Authentication, DeliveryAttempt, LogoutDelivery, UserSession};
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
@@ -24,13 +44,39 @@ pub struct User {
     middle_name: NonEmptyString,
     name: NonEmptyString,
     nickname: NonEmptyString,
+    #[serde(default, skip_serializing)]
+    password: Option<NonEmptyString>,
     personio_email: NonEmptyString,
     phone_number: NonEmptyString,
     preferred_username: NonEmptyString,
+    #[serde(default, skip_serializing)]
+    pub totp_secret: Option<TotpSecret>,
     updated_at: u64,
     zoneinfo: NonEmptyString,
 }
 
+impl User {
+    // How the user is presented on the account selection page
+    pub fn account(&self) -> String {
+        format!("{} <{}>", self.name, self.email)
+    }
+
+    // A stable subject identifier, so that consents and sessions outlive a
+    // single login
+    pub fn id(&self) -> SecureString {
+        hash(&self.preferred_username).try_into().unwrap()
+    }
+
+    // The user is known by either the preferred username or the email
+    pub fn identified_by(&self, username: &NonEmptyString) -> bool {
+        *username == self.preferred_username || *username == self.email
+    }
+
+    pub fn verify_password(&self, username: &NonEmptyString, password: &NonEmptyString) -> bool {
+        self.identified_by(username) && self.password.as_ref() == Some(password)
+    }
+}
+
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Address {
     country: NonEmptyString,
@@ -40,39 +86,136 @@ pub struct Address {
     street_address: NonEmptyString,
 }
 
-pub struct AuthSession {
+#[derive(Clone)]
+pub struct Grant {
+    pub auth_time: UnixTime,
+    pub authentication: Authentication,
+    pub authorization_details: Vec<AuthorizationDetail>,
     pub client_id: SecureString,
-    pub code_challenge: CodeChallenge,
-    pub redirect_uri: Uri,
+    pub nonce: Option<NonEmptyString>,
+    pub resource: Vec<Uri>,
     pub scope: HashSet<Scope>,
+    pub sid: Option<SecureString>,
     pub user_id: SecureString,
 }
 
+pub struct AuthSession {
+    pub code_challenge: CodeChallenge,
+    pub grant: Grant,
+    pub redirect_uri: Uri,
+}
+
+// An authorization request waiting for the user to interact with the server
+pub enum Interaction {
+    SelectAccount {
+        login_required: bool,
+        params: AuthorizeParams,
+        sid: Option<SecureString>,
+    },
+    Login {
+        attempts: u32,
+        expires_at: UnixTime,
+        params: AuthorizeParams,
+        sid: Option<SecureString>,
+    },
+    Consent {
+        grant: Box<Grant>,
+        params: AuthorizeParams,
+    },
+}
+
+impl Interaction {
+    // The pages without an expiration wait for the user indefinitely
+    pub fn is_expired(&self) -> bool {
+        match self {
+            Self::Login { expires_at, .. } => expires_at.expired(),
+            _ => false,
+        }
+    }
+}
+
+pub struct PushedRequest {
+    pub expires_at: UnixTime,
+    pub params: AuthorizeParams,
+}
+
 pub struct Vars {
+    pub access_token_format: AccessTokenFormat,
+    pub access_token_profile: AccessTokenProfile,
     pub audience: Uri,
+    pub backchannel_logout_attempts: u32,
+    pub ciba_expiration: u64,
+    pub ciba_interval: u64,
     pub client_secret: SecureString,
+    pub consent_required: bool,
+    pub device_expiration: u64,
+    pub device_interval: u64,
+    pub dpop_nonce_expiration: u64,
+    pub dpop_nonce_required: bool,
     pub expiration: u64,
     pub issuer: Uri,
-    pub secret: Secret,
+    pub jarm_expiration: u64,
+    pub otp_acr: NonEmptyString,
+    pub otp_attempts: u32,
+    pub otp_expiration: u64,
+    pub par_expiration: u64,
     pub required_scopes: HashSet<Scope>,
+    pub session_expiration: u64,
+    pub userinfo_acr_values: Vec<NonEmptyString>,
 }
 
 impl From<Configuration> for Vars {
     fn from(config: Configuration) -> Self {
         Self {
+            access_token_format: config.access_token_format,
+            access_token_profile: config.access_token_profile,
             audience: config.audience,
+            backchannel_logout_attempts: config.backchannel_logout_attempts,
+            ciba_expiration: config.ciba_expiration,
+            ciba_interval: config.ciba_interval,
             client_secret: config.client_secret,
+            consent_required: config.consent_required,
+            device_expiration: config.device_expiration,
+            device_interval: config.device_interval,
+            dpop_nonce_expiration: config.dpop_nonce_expiration,
+            dpop_nonce_required: config.dpop_nonce_required,
             expiration: config.expiration,
             issuer: config.issuer,
-            secret: config.secret,
+            jarm_expiration: config.jarm_expiration,
+            otp_acr: config.otp_acr,
+            otp_attempts: config.otp_attempts,
+            otp_expiration: config.otp_expiration,
+            par_expiration: config.par_expiration,
             required_scopes: [Scope::Profile, Scope::Email, Scope::Address, Scope::Phone].into(),
+            session_expiration: config.session_expiration,
+            userinfo_acr_values: config.userinfo_acr_values,
         }
     }
 }
 
 pub struct State {
+    access_tokens: HashMap<SecureString, AccessToken>,
     auth_sessions: HashMap<SecureString, AuthSession>,
+    authentication: Authentication,
+    authorization_details_types: HashMap<NonEmptyString, AuthorizationDetailsType>,
+    backchannel_requests: HashMap<SecureString, BackchannelRequest>,
+    clients: HashMap<SecureString, Client>,
+    consents: HashMap<(SecureString, SecureString), Consent>,
+    device_sessions: HashMap<SecureString, DeviceSession>,
+    dpop_jtis: HashMap<NonEmptyString, UnixTime>,
+    dpop_nonces: HashMap<SecureString, UnixTime>,
+    interactions: HashMap<SecureString, Interaction>,
+    keys: KeySet,
+    logout_deliveries: Vec<LogoutDelivery>,
+    pushed_requests: HashMap<NonEmptyString, PushedRequest>,
+    registrations: HashMap<SecureString, Registration>,
+    // The ids of the deleted clients, which must not fall back to unregistered clients
+    deleted_clients: HashSet<SecureString>,
+    resources: HashMap<Uri, ResourceServer>,
     rng: StdRng,
+    sessions: HashMap<SecureString, UserSession>,
+    // The last time step whose one time password was used, which cannot be replayed
+    totp_step: Option<u64>,
     user: User,
     vars: Vars,
 }
@@ -95,6 +238,335 @@ impl AppState {
             .insert(code, session);
     }
 
+    pub fn get_pushed_request(&self, request_uri: &NonEmptyString) -> Option<PushedRequest> {
+        self.as_ref()
+            .write()
+            .unwrap()
+            .pushed_requests
+            .remove(request_uri)
+            .filter(|request| !request.expires_at.expired())
+    }
+
+    pub fn set_pushed_request(&self, request_uri: NonEmptyString, request: PushedRequest) {
+        self.as_ref()
+            .write()
+            .unwrap()
+            .pushed_requests
+            .insert(request_uri, request);
+    }
+
+    pub fn get_interaction(&self, interaction_id: &SecureString) -> Option<Interaction> {
+        self.as_ref()
+            .write()
+            .unwrap()
+            .interactions
+            .remove(interaction_id)
+    }
+
+    pub fn set_interaction(&self, interaction_id: SecureString, interaction: Interaction) {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.interactions
+            .retain(|_, interaction| !interaction.is_expired());
+        lock.interactions.insert(interaction_id, interaction);
+    }
+
+    // Only the one time passwords of later time steps are accepted
+    // https://datatracker.ietf.org/doc/html/rfc6238#section-5.2
+    pub fn use_totp_step(&self, step: u64) -> bool {
+        let mut lock = self.as_ref().write().unwrap();
+        if lock.totp_step.is_some_and(|used| step <= used) {
+            return false;
+        }
+        lock.totp_step = Some(step);
+        true
+    }
+
+    // The scopes granted by a remembered consent of the user to the client
+    pub fn consented_scopes(
+        &self,
+        user_id: &SecureString,
+        client_id: &SecureString,
+        requested: &HashSet<Scope>,
+    ) -> Option<HashSet<Scope>> {
+        self.as_ref()
+            .read()
+            .unwrap()
+            .consents
+            .get(&(user_id.clone(), client_id.clone()))
+            .and_then(|consent| consent.apply(requested))
+    }
+
+    pub fn set_consent(&self, user_id: &SecureString, client_id: &SecureString, consent: Consent) {
+        self.as_ref()
+            .write()
+            .unwrap()
+            .consents
+            .insert((user_id.clone(), client_id.clone()), consent);
+    }
+
+    // Keeps the claims of an opaque access token until it expires
+    pub fn set_access_token(&self, reference: SecureString, access_token: AccessToken) {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.access_tokens.retain(|_, token| !token.exp.expired());
+        lock.access_tokens.insert(reference, access_token);
+    }
+
+    pub fn access_token(&self, reference: &str) -> Option<AccessToken> {
+        let reference = SecureString::try_from(reference).ok()?;
+        let lock = self.as_ref().read().unwrap();
+        let access_token = lock.access_tokens.get(&reference)?;
+        (!access_token.exp.expired()).then(|| access_token.clone())
+    }
+
+    // Records the `jti` of a DPoP proof until it expires, returning false on replays
+    pub fn use_dpop_jti(&self, jti: NonEmptyString, expires_at: UnixTime) -> bool {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.dpop_jtis.retain(|_, expires_at| !expires_at.expired());
+        lock.dpop_jtis.insert(jti, expires_at).is_none()
+    }
+
+    // Issues a nonce valid for `DPOP_NONCE_EXPIRATION`, dropping the expired ones
+    pub fn dpop_nonce(&self) -> SecureString {
+        let nonce = self.gen_secure_string();
+        let expires_at = UnixTime::now() + self.dpop_nonce_expiration();
+        let mut lock = self.as_ref().write().unwrap();
+        lock.dpop_nonces
+            .retain(|_, expires_at| !expires_at.expired());
+        lock.dpop_nonces.insert(nonce.clone(), expires_at);
+        nonce
+    }
+
+    pub fn is_dpop_nonce(&self, nonce: &SecureString) -> bool {
+        let lock = self.0.read().unwrap();
+        lock.dpop_nonces
+            .get(nonce)
+            .is_some_and(|expires_at| !expires_at.expired())
+    }
+
+    pub fn create_session(
+        &self,
+        client_id: &SecureString,
+        user_id: &SecureString,
+        authentication: Authentication,
+    ) -> (SecureString, UserSession) {
+        let sid = self.gen_secure_string();
+        let mut session = UserSession::new(user_id.clone(), authentication);
+        session.clients.insert(client_id.clone());
+        let mut lock = self.as_ref().write().unwrap();
+        let lifetime = lock.vars.session_expiration;
+        lock.sessions
+            .retain(|_, session| !session.is_expired(lifetime));
+        lock.sessions.insert(sid.clone(), session.clone());
+        (sid, session)
+    }
+
+    pub fn user_session(&self, sid: &SecureString) -> Option<UserSession> {
+        let lock = self.as_ref().read().unwrap();
+        let lifetime = lock.vars.session_expiration;
+        lock.sessions
+            .get(sid)
+            .filter(|session| !session.is_expired(lifetime))
+            .cloned()
+    }
+
+    // Adds the client to an existing session, recording the new authentication of the user if any
+    pub fn join_session(
+        &self,
+        sid: &SecureString,
+        client_id: &SecureString,
+        authentication: Option<Authentication>,
+    ) -> Option<UserSession> {
+        let mut lock = self.as_ref().write().unwrap();
+        let lifetime = lock.vars.session_expiration;
+        let session = lock
+            .sessions
+            .get_mut(sid)
+            .filter(|session| !session.is_expired(lifetime))?;
+        session.active_at = UnixTime::now();
+        session.clients.insert(client_id.clone());
+        if let Some(authentication) = authentication {
+            session.auth_time = UnixTime::now();
+            session.authentication = authentication;
+        }
+        Some(session.clone())
+    }
+
+    pub fn find_session(&self, user_id: &SecureString) -> Option<SecureString> {
+        let lock = self.as_ref().read().unwrap();
+        let lifetime = lock.vars.session_expiration;
+        lock.sessions
+            .iter()
+            .find(|(_, session)| &session.user_id == user_id && !session.is_expired(lifetime))
+            .map(|(sid, _)| sid.clone())
+    }
+
+    pub fn end_session(&self, sid: &SecureString) -> Option<UserSession> {
+        self.as_ref().write().unwrap().sessions.remove(sid)
+    }
+
+    pub fn add_logout_delivery(&self, delivery: LogoutDelivery) -> usize {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.logout_deliveries.push(delivery);
+        lock.logout_deliveries.len() - 1
+    }
+
+    pub fn record_logout_attempt(&self, index: usize, attempt: DeliveryAttempt, delivered: bool) {
+        let mut lock = self.as_ref().write().unwrap();
+        let delivery = &mut lock.logout_deliveries[index];
+        delivery.attempts.push(attempt);
+        delivery.delivered = delivered;
+    }
+
+    pub fn logout_deliveries(&self) -> Vec<LogoutDelivery> {
+        self.as_ref().read().unwrap().logout_deliveries.clone()
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc7591#section-3.2.1
+    pub fn register_client(&self, client: Client, registration: Registration) {
+        let mut lock = self.as_ref().write().unwrap();
+        let client_id = client.client_id.clone();
+        lock.registrations.insert(client_id.clone(), registration);
+        lock.clients.insert(client_id, client);
+    }
+
+    pub fn registration(&self, client_id: &SecureString) -> Option<Registration> {
+        self.as_ref()
+            .read()
+            .unwrap()
+            .registrations
+            .get(client_id)
+            .cloned()
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc7592#section-2.3
+    pub fn unregister_client(&self, client_id: &SecureString) {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.registrations.remove(client_id);
+        lock.clients.remove(client_id);
+        lock.deleted_clients.insert(client_id.clone());
+    }
+
+    pub fn is_deleted_client(&self, client_id: &SecureString) -> bool {
+        self.0.read().unwrap().deleted_clients.contains(client_id)
+    }
+
+    pub fn gen_client_id(&self) -> SecureString {
+        let mut lock = self.as_ref().write().unwrap();
+        url_safe_random_bytes(&mut lock.rng).try_into().unwrap()
+    }
+
+    pub fn client(&self, client_id: &SecureString) -> Client {
+        self.as_ref()
+            .read()
+            .unwrap()
+            .clients
+            .get(client_id)
+            .cloned()
+            .unwrap_or_else(|| Client::unregistered(client_id.clone()))
+    }
+
+    // Expired sessions are pruned
+    pub fn set_device_session(&self, device_code: SecureString, session: DeviceSession) {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.device_sessions
+            .retain(|_, session| !session.pending.is_expired());
+        lock.device_sessions.insert(device_code, session);
+    }
+
+    // Only the client that made the request may redeem it
+    // https://datatracker.ietf.org/doc/html/rfc8628#section-3.4
+    pub fn poll_device_session(
+        &self,
+        device_code: &SecureString,
+        client_id: &SecureString,
+    ) -> Result<Grant, PollError> {
+        let mut lock = self.as_ref().write().unwrap();
+        let session = lock
+            .device_sessions
+            .get_mut(device_code)
+            .filter(|session| &session.pending.grant.client_id == client_id)
+            .ok_or(PollError::InvalidGrant)?;
+        let result = session.pending.poll();
+        if !matches!(
+            result,
+            Err(PollError::AuthorizationPending | PollError::SlowDown)
+        ) {
+            lock.device_sessions.remove(device_code);
+        }
+        result
+    }
+
+    pub fn resolve_device_session(&self, user_code: &UserCode, approved: bool) -> bool {
+        self.as_ref()
+            .write()
+            .unwrap()
+            .device_sessions
+            .values_mut()
+            .find(|session| &session.user_code == user_code && session.pending.is_pending())
+            .map(|session| session.pending.resolve(approved))
+            .is_some()
+    }
+
+    // Expired requests are pruned, including the denied ones never polled
+    pub fn set_backchannel_request(&self, auth_req_id: SecureString, request: BackchannelRequest) {
+        let mut lock = self.as_ref().write().unwrap();
+        lock.backchannel_requests
+            .retain(|_, request| !request.pending.is_expired());
+        lock.backchannel_requests.insert(auth_req_id, request);
+    }
+
+    // https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#token_request
+    pub fn poll_backchannel_request(
+        &self,
+        auth_req_id: &SecureString,
+        client_id: &SecureString,
+    ) -> Result<Grant, PollError> {
+        let mut lock = self.as_ref().write().unwrap();
+        let request = lock
+            .backchannel_requests
+            .get_mut(auth_req_id)
+            .filter(|request| &request.pending.grant.client_id == client_id)
+            .ok_or(PollError::InvalidGrant)?;
+        let result = request.pending.poll();
+        if !matches!(
+            result,
+            Err(PollError::AuthorizationPending | PollError::SlowDown)
+        ) {
+            lock.backchannel_requests.remove(auth_req_id);
+        }
+        result
+    }
+
+    // Returns the client of the request and the token of its ping notification
+    pub fn resolve_backchannel_request(
+        &self,
+        auth_req_id: &SecureString,
+        approved: bool,
+    ) -> Option<(SecureString, Option<NonEmptyString>)> {
+        let mut lock = self.as_ref().write().unwrap();
+        let request = lock
+            .backchannel_requests
+            .get_mut(auth_req_id)
+            .filter(|request| request.pending.is_pending())?;
+        request.pending.resolve(approved);
+        let client_id = request.pending.grant.client_id.clone();
+        Some((client_id, request.client_notification_token.clone()))
+    }
+
+    pub fn pending_backchannel_requests(&self) -> Vec<PendingRequest> {
+        let lock = self.as_ref().read().unwrap();
+        let requests = lock.backchannel_requests.iter();
+        requests
+            .filter(|(_, request)| request.pending.is_pending())
+            .map(|(auth_req_id, request)| PendingRequest::new(auth_req_id, request))
+            .collect()
+    }
+
+    pub fn gen_user_code(&self) -> UserCode {
+        let mut lock = self.as_ref().write().unwrap();
+        UserCode::generate(&mut lock.rng)
+    }
+
     pub fn get_user(&self) -> User {
         self.as_ref().read().unwrap().user.clone()
     }
@@ -104,12 +576,101 @@ impl AppState {
         strong_random_bytes(&mut lock.rng).try_into().unwrap()
     }
 
+    pub fn authentication(&self) -> Authentication {
+        self.0.read().unwrap().authentication.clone()
+    }
+
+    pub fn set_authentication(&self, authentication: Authentication) {
+        self.as_ref().write().unwrap().authentication = authentication;
+    }
+
+    pub fn access_token_format(&self, client_id: &SecureString) -> AccessTokenFormat {
+        let default = self.0.read().unwrap().vars.access_token_format;
+        self.client(client_id)
+            .access_token_format
+            .unwrap_or(default)
+    }
+
+    pub fn access_token_profile(&self, client_id: &SecureString) -> AccessTokenProfile {
+        let default = self.0.read().unwrap().vars.access_token_profile;
+        self.client(client_id)
+            .access_token_profile
+            .unwrap_or(default)
+    }
+
     pub fn audience(&self) -> Uri {
         self.0.read().unwrap().vars.audience.clone()
     }
 
-    pub fn client_secret(&self) -> SecureString {
-        self.0.read().unwrap().vars.client_secret.clone()
+    pub fn validate_authorization_details(
+        &self,
+        details: &[AuthorizationDetail],
+    ) -> Result<(), authorization_details::Error> {
+        let lock = self.0.read().unwrap();
+        authorization_details::validate(details, &lock.authorization_details_types)
+    }
+
+    // The default audience is always a valid resource
+    pub fn is_resource(&self, uri: &Uri) -> bool {
+        *uri == self.audience() || self.0.read().unwrap().resources.contains_key(uri)
+    }
+
+    // https://datatracker.ietf.org/doc/html/rfc8707#section-2.2
+    pub fn token_audience(&self, resources: &[Uri]) -> Audience {
+        if resources.is_empty() {
+            return Audience::new(self.audience(), self.expiration());
+        }
+        let lock = self.0.read().unwrap();
+        // The default audience accepts every scope
+        let servers = resources.iter().map(|uri| {
+            lock.resources.get(uri).cloned().unwrap_or(ResourceServer {
+                expiration: None,
+                scopes: Scope::all(),
+                uri: uri.clone(),
+            })
+        });
+        Audience::resources(servers.collect(), lock.vars.expiration)
+    }
+
+    pub fn backchannel_logout_attempts(&self) -> u32 {
+        self.0.read().unwrap().vars.backchannel_logout_attempts
+    }
+
+    // Deleted clients have no secret, so they fail every authentication
+    pub fn client_secret(&self, client_id: &SecureString) -> Option<SecureString> {
+        if self.is_deleted_client(client_id) {
+            return None;
+        }
+        let default = self.0.read().unwrap().vars.client_secret.clone();
+        Some(self.client(client_id).client_secret.unwrap_or(default))
+    }
+
+    pub fn consent_required(&self) -> bool {
+        self.0.read().unwrap().vars.consent_required
+    }
+
+    pub fn dpop_nonce_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.dpop_nonce_expiration
+    }
+
+    pub fn dpop_nonce_required(&self) -> bool {
+        self.0.read().unwrap().vars.dpop_nonce_required
+    }
+
+    pub fn ciba_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.ciba_expiration
+    }
+
+    pub fn ciba_interval(&self) -> u64 {
+        self.0.read().unwrap().vars.ciba_interval
+    }
+
+    pub fn device_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.device_expiration
+    }
+
+    pub fn device_interval(&self) -> u64 {
+        self.0.read().unwrap().vars.device_interval
     }
 
     pub fn expiration(&self) -> u64 {
@@ -120,20 +681,110 @@ impl AppState {
         self.0.read().unwrap().vars.issuer.clone()
     }
 
-    pub fn secret(&self) -> Secret {
-        self.0.read().unwrap().vars.secret.clone()
+    // The absolute URL of an endpoint of the server, which is reachable at the issuer
+    pub fn endpoint(&self, path: &str) -> String {
+        let issuer = self.issuer().to_string();
+        format!("{}{path}", issuer.trim_end_matches('/'))
+    }
+
+    pub fn jarm_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.jarm_expiration
+    }
+
+    pub fn otp_acr(&self) -> NonEmptyString {
+        self.0.read().unwrap().vars.otp_acr.clone()
+    }
+
+    pub fn otp_attempts(&self) -> u32 {
+        self.0.read().unwrap().vars.otp_attempts
+    }
+
+    pub fn otp_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.otp_expiration
+    }
+
+    pub fn par_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.par_expiration
+    }
+
+    pub fn session_expiration(&self) -> u64 {
+        self.0.read().unwrap().vars.session_expiration
+    }
+
+    pub fn signing_key(&self) -> SigningKey {
+        self.0.read().unwrap().keys.active().clone()
+    }
+
+    pub fn verification_key(&self, kid: &str) -> Option<SigningKey> {
+        self.0.read().unwrap().keys.find(kid).cloned()
+    }
+
+    pub fn keys(&self) -> Vec<SigningKey> {
+        self.0.read().unwrap().keys.keys().cloned().collect()
+    }
+
+    pub fn rotate_keys(&self) {
+        let mut lock = self.as_ref().write().unwrap();
+        let State {
+            keys,
+            resources,
+            rng,
+            vars,
+            ..
+        } = &mut *lock;
+        // The longest lifetime of the tokens signed by the active key
+        let lifetime = resources
+            .values()
+            .filter_map(|server| server.expiration)
+            .fold(vars.expiration, u64::max);
+        keys.rotate(rng, lifetime);
     }
 
     pub fn required_scopes(&self) -> HashSet<Scope> {
         self.0.read().unwrap().vars.required_scopes.clone()
     }
+
+    pub fn userinfo_acr_values(&self) -> Vec<NonEmptyString> {
+        self.0.read().unwrap().vars.userinfo_acr_values.clone()
+    }
 }
 
 impl From<Configuration> for AppState {
     fn from(configuration: Configuration) -> Self {
+        let mut rng = StdRng::seed_from_u64(configuration.rng_seed);
         let state = State {
+            access_tokens: Default::default(),
             auth_sessions: Default::default(),
-            rng: StdRng::seed_from_u64(configuration.rng_seed),
+            authentication: configuration.authentication.clone(),
+            authorization_details_types: configuration
+                .authorization_details_types
+                .values()
+                .map(|schema| (schema.r#type.clone(), schema.clone()))
+                .collect(),
+            backchannel_requests: Default::default(),
+            clients: configuration
+                .clients
+                .values()
+                .map(|client| (client.client_id.clone(), client.clone()))
+                .collect(),
+            consents: Default::default(),
+            device_sessions: Default::default(),
+            dpop_jtis: Default::default(),
+            dpop_nonces: Default::default(),
+            interactions: Default::default(),
+            keys: KeySet::new(configuration.secret.clone(), &mut rng),
+            logout_deliveries: Default::default(),
+            pushed_requests: Default::default(),
+            registrations: Default::default(),
+            deleted_clients: Default::default(),
+            resources: configuration
+                .resources
+                .values()
+                .map(|resource| (resource.uri.clone(), resource.clone()))
+                .collect(),
+            rng,
+            sessions: Default::default(),
+            totp_step: None,
             user: configuration.user.clone(),
             vars: configuration.into(),
         };
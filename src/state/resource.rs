@@ -0,0 +1,93 @@
+use crate::data::Scope;
+use axum::http::Uri;
+use serde::Deserialize;
+use serde_with::formats::SpaceSeparator;
+use serde_with::{serde_as, StringWithSeparator};
+use std::collections::HashSet;
+
+// A protected resource that clients can name with the `resource` parameter
+// https://datatracker.ietf.org/doc/html/rfc8707#section-2
+#[serde_as]
+#[derive(Clone, Deserialize)]
+pub struct ResourceServer {
+    #[serde(default)]
+    pub expiration: Option<u64>,
+    #[serde_as(as = "StringWithSeparator<SpaceSeparator, Scope>")]
+    pub scopes: HashSet<Scope>,
+    #[serde(with = "http_serde::uri")]
+    pub uri: Uri,
+}
+
+// The audiences of an access token with the scopes and lifetime they accept
+pub struct Audience {
+    pub aud: Vec<Uri>,
+    pub expiration: u64,
+    scopes: Option<HashSet<Scope>>,
+}
+
+impl Audience {
+    // The default audience accepts every scope
+    pub fn new(aud: Uri, expiration: u64) -> Self {
+        Self {
+            aud: vec![aud],
+            expiration,
+            scopes: None,
+        }
+    }
+
+    // A token for several resources lives as long as the shortest lived one
+    // and carries the scopes accepted by any of them
+    pub fn resources(servers: Vec<ResourceServer>, expiration: u64) -> Self {
+        let expiration = servers
+            .iter()
+            .map(|server| server.expiration.unwrap_or(expiration))
+            .min()
+            .unwrap_or(expiration);
+        let scopes = servers.iter().flat_map(|server| server.scopes.clone());
+        Self {
+            scopes: Some(scopes.collect()),
+            aud: servers.into_iter().map(|server| server.uri).collect(),
+            expiration,
+        }
+    }
+
+    pub fn scope(&self, granted: &HashSet<Scope>) -> HashSet<Scope> {
+        match &self.scopes {
+            Some(scopes) => granted.intersection(scopes).cloned().collect(),
+            None => granted.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn server(uri: &'static str, scopes: &[Scope], expiration: Option<u64>) -> ResourceServer {
+        ResourceServer {
+            expiration,
+            scopes: scopes.iter().cloned().collect(),
+            uri: Uri::from_static(uri),
+        }
+    }
+
+    #[test]
+    fn default_audience_keeps_scope() {
+        let audience = Audience::new(Uri::from_static("api.example.com"), 60);
+        let granted = [Scope::Openid, Scope::Email].into();
+        assert_eq!(audience.scope(&granted), granted);
+    }
+
+    #[test]
+    fn resources_restrict_scope_and_lifetime() {
+        let servers = vec![
+            server("https://a.example.com/", &[Scope::Email], Some(30)),
+            server("https://b.example.com/", &[Scope::Phone], None),
+        ];
+        let audience = Audience::resources(servers, 60);
+        assert_eq!(audience.aud.len(), 2);
+        assert_eq!(audience.expiration, 30);
+        let granted = [Scope::Openid, Scope::Email, Scope::Profile].into();
+        assert_eq!(audience.scope(&granted), [Scope::Email].into());
+    }
+}
@@ -0,0 +1,111 @@
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::data::time::UnixTime;
+use crate::data::AuthenticationMethod;
+use axum::http::{HeaderValue, Uri};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+pub const SESSION_COOKIE: &str = "oidcms_session";
+
+// The outcome of the simulated authentication of the user
+// https://openid.net/specs/openid-connect-core-1_0.html#IDToken
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Authentication {
+    pub acr: NonEmptyString,
+    pub amr: Vec<AuthenticationMethod>,
+}
+
+// https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken
+#[derive(Clone)]
+pub struct UserSession {
+    pub active_at: UnixTime,
+    pub auth_time: UnixTime,
+    pub authentication: Authentication,
+    pub clients: HashSet<SecureString>,
+    pub user_id: SecureString,
+}
+
+impl UserSession {
+    pub fn new(user_id: SecureString, authentication: Authentication) -> Self {
+        let now = UnixTime::now();
+        Self {
+            active_at: now,
+            auth_time: now,
+            authentication,
+            clients: HashSet::new(),
+            user_id,
+        }
+    }
+
+    // https://openid.net/specs/openid-connect-core-1_0.html#AuthRequest
+    pub fn is_older_than(&self, max_age: u64) -> bool {
+        (self.auth_time + max_age).expired()
+    }
+
+    // The session ends when the browser stays inactive for its whole lifetime
+    pub fn is_expired(&self, lifetime: u64) -> bool {
+        (self.active_at + lifetime).expired()
+    }
+}
+
+pub fn session_cookie(sid: &SecureString, lifetime: u64) -> HeaderValue {
+    let cookie = format!(
+        "{SESSION_COOKIE}={sid}; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age={lifetime}"
+    );
+    HeaderValue::from_str(&cookie).unwrap()
+}
+
+pub fn expired_session_cookie() -> HeaderValue {
+    let cookie = format!("{SESSION_COOKIE}=; Path=/; HttpOnly; Secure; SameSite=Lax; Max-Age=0");
+    HeaderValue::from_str(&cookie).unwrap()
+}
+
+#[derive(Clone, Serialize)]
+pub struct LogoutDelivery {
+    pub attempts: Vec<DeliveryAttempt>,
+    pub client_id: SecureString,
+    pub delivered: bool,
+    pub sid: SecureString,
+    #[serde(with = "http_serde::uri")]
+    pub uri: Uri,
+}
+
+#[derive(Clone, Serialize)]
+pub struct DeliveryAttempt {
+    pub at: UnixTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn session() -> UserSession {
+        let authentication = Authentication {
+            acr: "1".try_into().unwrap(),
+            amr: vec![AuthenticationMethod::Pwd],
+        };
+        UserSession::new(
+            "L+cqfFYoFThziwficu6lEZQQlzo=".try_into().unwrap(),
+            authentication,
+        )
+    }
+
+    #[test]
+    fn fresh_session() {
+        assert!(!session().is_older_than(60))
+    }
+
+    #[test]
+    fn max_age_zero() {
+        assert!(session().is_older_than(0))
+    }
+
+    #[test]
+    fn inactive_session_expired() {
+        assert!(!session().is_expired(60) && session().is_expired(0))
+    }
+}
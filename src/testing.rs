@@ -0,0 +1,185 @@
+// Helpers to exercise the endpoints from the unit tests
+use crate::config::Configuration;
+use crate::mtls::ClientCertificate;
+use crate::state::AppState;
+use axum::body::{to_bytes, Body};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, HOST, LOCATION};
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+pub const CLIENT_ID: &str = "0jrabyQWm4B9zVJPbotY";
+pub const CLIENT_SECRET: &str = "6W7XvLCrs4ByKn7Ucwh8ygeeXRhdGFdVOTp75eOc";
+pub const CODE_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+pub const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+pub const REDIRECT_URI: &str = "http://localhost:3000/success";
+
+pub struct TestResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl TestResponse {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap()
+    }
+
+    pub fn header(&self, name: &str) -> &str {
+        self.headers[name].to_str().unwrap()
+    }
+
+    // The parameters of a redirection, in the query or in the fragment
+    pub fn redirect_params(&self) -> Vec<(String, String)> {
+        let location = self.header(LOCATION.as_str());
+        let (_, params) = location.split_once(['?', '#']).unwrap();
+        serde_urlencoded::from_str(params).unwrap()
+    }
+
+    pub fn redirect_param(&self, name: &str) -> Option<String> {
+        self.redirect_params()
+            .into_iter()
+            .find_map(|(key, value)| (key == name).then_some(value))
+    }
+}
+
+// The defaults of the Dockerfile with some variables overridden
+pub fn state(overrides: &[(&str, &str)]) -> AppState {
+    AppState::from(Configuration::test(overrides))
+}
+
+// The defaults of a test module with some variables overridden
+pub fn state_with(defaults: &[(&str, &str)], overrides: &[(&str, &str)]) -> AppState {
+    state(&merge(defaults, overrides))
+}
+
+// The overrides replace the defaults of the same name instead of repeating them
+pub fn merge<'a>(
+    defaults: &[(&'a str, &'a str)],
+    overrides: &[(&'a str, &'a str)],
+) -> Vec<(&'a str, &'a str)> {
+    let mut merged = defaults.to_vec();
+    merged.retain(|(name, _)| overrides.iter().all(|(overridden, _)| name != overridden));
+    merged.extend_from_slice(overrides);
+    merged
+}
+
+pub async fn send(state: &AppState, request: Request<Body>) -> TestResponse {
+    let response = crate::router(state.clone()).oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    TestResponse {
+        status,
+        headers,
+        body: String::from_utf8(body.to_vec()).unwrap(),
+    }
+}
+
+pub fn request(method: Method, uri: &str) -> axum::http::request::Builder {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(HOST, "localhost:4000")
+}
+
+pub async fn get(state: &AppState, uri: &str) -> TestResponse {
+    send(
+        state,
+        request(Method::GET, uri).body(Body::empty()).unwrap(),
+    )
+    .await
+}
+
+fn form(uri: &str, params: &[(&str, &str)]) -> Request<Body> {
+    request(Method::POST, uri)
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .body(Body::from(serde_urlencoded::to_string(params).unwrap()))
+        .unwrap()
+}
+
+pub async fn post_form(state: &AppState, uri: &str, params: &[(&str, &str)]) -> TestResponse {
+    send(state, form(uri, params)).await
+}
+
+// A form posted over a TLS connection where the client presented the certificate
+pub async fn post_form_with_certificate(
+    state: &AppState,
+    uri: &str,
+    params: &[(&str, &str)],
+    certificate: ClientCertificate,
+) -> TestResponse {
+    let mut request = form(uri, params);
+    request.extensions_mut().insert(certificate);
+    send(state, request).await
+}
+
+// Only the thumbprint of a self signed certificate is checked, whatever its bytes
+pub fn client_certificate() -> ClientCertificate {
+    ClientCertificate {
+        der: b"self signed client certificate".to_vec(),
+        trusted: false,
+    }
+}
+
+// The `JWKS` registering the client certificate for `self_signed_tls_client_auth`
+pub fn client_certificate_jwks() -> String {
+    json!({"keys": [{
+        "kty": "EC",
+        "crv": "P-256",
+        "x": "O6XSRO7aPEy5zEHY4W-nEujWXuVmGDWFW8dXilR6jqY",
+        "y": "qlBFgf4MgjDUR1zVla7sI_06mwFR6pzbW8bCsllm7Tw",
+        "x5t#S256": client_certificate().thumbprint(),
+    }]})
+    .to_string()
+}
+
+// A direct authorization request of the code flow
+pub async fn authorize(state: &AppState, client_id: &str) -> TestResponse {
+    let query = serde_urlencoded::to_string([
+        ("client_id", client_id),
+        ("code_challenge", CODE_CHALLENGE),
+        ("code_challenge_method", "S256"),
+        ("redirect_uri", REDIRECT_URI),
+        ("response_mode", "query"),
+        ("response_type", "code"),
+        ("scope", "openid email"),
+        ("state", "12345678901234567890"),
+    ])
+    .unwrap();
+    get(state, &format!("/authorize?{query}")).await
+}
+
+// Authorizes the client with the code flow and returns the code
+pub async fn authorization_code(state: &AppState, client_id: &str) -> String {
+    let response = authorize(state, client_id).await;
+    assert_eq!(response.status, StatusCode::FOUND, "{}", response.body);
+    response.redirect_param("code").unwrap()
+}
+
+// An access token of the code flow with the `openid email` scope
+pub async fn access_token(state: &AppState, client_id: &str) -> String {
+    let code = authorization_code(state, client_id).await;
+    let params = [
+        ("grant_type", "authorization_code"),
+        ("client_id", client_id),
+        ("client_secret", CLIENT_SECRET),
+        ("code", &code),
+        ("code_verifier", CODE_VERIFIER),
+        ("redirect_uri", REDIRECT_URI),
+    ];
+    let response = post_form(state, "/token", &params).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    response.json()["access_token"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+pub async fn userinfo(state: &AppState, access_token: &str) -> TestResponse {
+    let request = request(Method::GET, "/userinfo")
+        .header(AUTHORIZATION, format!("Bearer {access_token}"))
+        .body(Body::empty())
+        .unwrap();
+    send(state, request).await
+}
@@ -0,0 +1,317 @@
+use super::extractor::TokenExchangeParams;
+use super::issue;
+use super::jwt::{self, DecodeError};
+use crate::bounded_string::SecureString;
+use crate::data::access_token::{AccessToken, Actor, Confirmation};
+use crate::data::time::UnixTime;
+use crate::data::{Scope, TokenType};
+use crate::state::client::Client;
+use crate::state::AppState;
+use axum::http::Uri;
+use jsonwebtoken::Validation;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use thiserror::Error;
+
+// https://datatracker.ietf.org/doc/html/rfc8693#section-3
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum TokenTypeIdentifier {
+    #[serde(rename = "urn:ietf:params:oauth:token-type:access_token")]
+    AccessToken,
+    #[serde(rename = "urn:ietf:params:oauth:token-type:jwt")]
+    Jwt,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8693#section-2.2.1
+#[derive(Serialize)]
+pub struct TokenExchangeResponse {
+    access_token: String,
+    expires_in: u64,
+    issued_token_type: TokenTypeIdentifier,
+    scope: HashSet<Scope>,
+    token_type: TokenType,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("`actor_token_type` is required with `actor_token`")]
+    MissingActorTokenType,
+    #[error("invalid subject_token: {0}")]
+    SubjectToken(#[source] DecodeError),
+    #[error("invalid actor_token: {0}")]
+    ActorToken(#[source] DecodeError),
+    #[error("token issued by `{0}` instead of `{1}`")]
+    Issuer(Box<Uri>, Box<Uri>),
+    #[error("client `{0}` may not exchange tokens for audience `{1}`")]
+    Audience(SecureString, Uri),
+    #[error("client `{0}` may not impersonate subjects without an `actor_token`")]
+    Impersonation(SecureString),
+    #[error("requested scope exceeds the scope of the subject_token")]
+    Scope,
+    #[error("jwt encode error: `{0}`")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+}
+
+impl Error {
+    // https://datatracker.ietf.org/doc/html/rfc8693#section-2.2.2
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::MissingActorTokenType => "invalid_request",
+            Self::SubjectToken(_) | Self::ActorToken(_) | Self::Issuer(..) => "invalid_grant",
+            Self::Audience(..) => "invalid_target",
+            Self::Impersonation(_) => "unauthorized_client",
+            Self::Scope => "invalid_scope",
+            Self::Jwt(_) => "server_error",
+        }
+    }
+}
+
+pub fn exchange(
+    state: &AppState,
+    client: Client,
+    params: TokenExchangeParams,
+    cnf: Option<Confirmation>,
+) -> Result<TokenExchangeResponse, Error> {
+    let subject = decode(state, &params.subject_token).map_err(Error::SubjectToken)?;
+    verify_issuer(state, &subject)?;
+    // The requested audience is a resource server with its own scopes and
+    // token lifetime, without one the token is for the default audience
+    let resources: Vec<Uri> = params.audience.into_iter().collect();
+    let audience = state.token_audience(&resources);
+    if let Some(aud) = audience
+        .aud
+        .iter()
+        .find(|aud| !client.token_exchange_audiences.contains(aud))
+    {
+        return Err(Error::Audience(client.client_id, aud.clone()));
+    }
+    // Without an actor the client acts as the subject, otherwise the actor is
+    // recorded on top of any previous delegation chain
+    let act = match params.actor_token {
+        Some(actor_token) => {
+            params
+                .actor_token_type
+                .ok_or(Error::MissingActorTokenType)?;
+            let actor = decode(state, &actor_token).map_err(Error::ActorToken)?;
+            verify_issuer(state, &actor)?;
+            Some(Actor {
+                act: subject.act.map(Box::new),
+                sub: actor.sub,
+            })
+        }
+        None if client.token_exchange_impersonation => subject.act,
+        None => return Err(Error::Impersonation(client.client_id)),
+    };
+    let scope = match params.scope {
+        Some(scope) if scope.is_subset(&subject.scp) => scope,
+        Some(_) => return Err(Error::Scope),
+        None => subject.scp,
+    };
+    let scope = audience.scope(&scope);
+    let now = UnixTime::now();
+    let access_token = AccessToken {
+        acr: subject.acr,
+        act,
+        aud: audience.aud,
+        auth_time: subject.auth_time,
+        authorization_details: subject.authorization_details,
+        cid: client.client_id,
+        cnf,
+        exp: now + audience.expiration,
+        iat: now,
+        iss: state.issuer(),
+        jti: state.gen_secure_string(),
+        scp: scope.clone(),
+        sub: subject.sub,
+        uid: subject.uid,
+        ver: 1,
+    };
+    let token_type = access_token.token_type();
+    Ok(TokenExchangeResponse {
+        access_token: issue::encode_access_token(state, access_token)?,
+        expires_in: audience.expiration,
+        // Both token types are the same JWT access token, so the issued type
+        // follows the request and falls back to the type of the subject_token
+        issued_token_type: params
+            .requested_token_type
+            .unwrap_or(params.subject_token_type),
+        scope,
+        token_type,
+    })
+}
+
+// Exchanged tokens may have been issued for any audience
+fn decode(state: &AppState, token: &SecureString) -> Result<AccessToken, DecodeError> {
+    if let Some(access_token) = state.access_token(token.as_ref()) {
+        return Ok(access_token);
+    }
+    let mut validation = Validation::default();
+    validation.validate_aud = false;
+    jwt::decode_access_token(state, token.as_ref(), &validation)
+}
+
+fn verify_issuer(state: &AppState, token: &AccessToken) -> Result<(), Error> {
+    if token.iss == state.issuer() {
+        Ok(())
+    } else {
+        Err(Error::Issuer(
+            Box::new(token.iss.clone()),
+            Box::new(state.issuer()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::AppState;
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET};
+    use axum::http::StatusCode;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::{json, Value};
+
+    const AUDIENCE: &str = "https://api.example.com/";
+    const ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+    fn state(overrides: &[(&str, &str)]) -> AppState {
+        let defaults = [
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__TOKEN_EXCHANGE_AUDIENCES", AUDIENCE),
+        ];
+        testing::state_with(&defaults, overrides)
+    }
+
+    async fn exchange(
+        state: &AppState,
+        subject_token: &str,
+        params: &[(&str, &str)],
+    ) -> testing::TestResponse {
+        let defaults = [
+            (
+                "grant_type",
+                "urn:ietf:params:oauth:grant-type:token-exchange",
+            ),
+            ("client_id", CLIENT_ID),
+            ("client_secret", CLIENT_SECRET),
+            ("audience", AUDIENCE),
+            ("subject_token", subject_token),
+            ("subject_token_type", ACCESS_TOKEN),
+        ];
+        testing::post_form(state, "/token", &testing::merge(&defaults, params)).await
+    }
+
+    fn claims(response: &testing::TestResponse) -> Value {
+        let access_token = response.json()["access_token"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        let claims = access_token.split('.').nth(1).unwrap();
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn delegation() {
+        let state = state(&[]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let actor_token = testing::access_token(&state, CLIENT_ID).await;
+        let params = [
+            ("actor_token", actor_token.as_str()),
+            ("actor_token_type", ACCESS_TOKEN),
+        ];
+        let response = exchange(&state, &subject_token, &params).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        let claims = claims(&response);
+        assert_eq!(claims["aud"], AUDIENCE);
+        assert!(claims["act"]["sub"].is_string());
+    }
+
+    #[tokio::test]
+    async fn actor_token_type_required() {
+        let state = state(&[]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let actor_token = testing::access_token(&state, CLIENT_ID).await;
+        let params = [("actor_token", actor_token.as_str())];
+        let response = exchange(&state, &subject_token, &params).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_request");
+    }
+
+    #[tokio::test]
+    async fn impersonation_refused() {
+        let state = state(&[]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = exchange(&state, &subject_token, &[]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "unauthorized_client");
+    }
+
+    #[tokio::test]
+    async fn impersonation_allowed() {
+        let state = state(&[("CLIENTS__WEB__TOKEN_EXCHANGE_IMPERSONATION", "true")]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = exchange(&state, &subject_token, &[]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(claims(&response).get("act").is_none());
+    }
+
+    #[tokio::test]
+    async fn audiences_overridden() {
+        let state = state(&[
+            (
+                "CLIENTS__WEB__TOKEN_EXCHANGE_AUDIENCES",
+                "https://other.example.com/",
+            ),
+            ("CLIENTS__WEB__TOKEN_EXCHANGE_IMPERSONATION", "true"),
+        ]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = exchange(&state, &subject_token, &[]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_target");
+        let params = [("audience", "https://other.example.com/")];
+        let response = exchange(&state, &subject_token, &params).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+    }
+
+    #[tokio::test]
+    async fn audience_not_allowed() {
+        let state = state(&[("CLIENTS__WEB__TOKEN_EXCHANGE_IMPERSONATION", "true")]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let params = [("audience", "https://other.example.com/")];
+        let response = exchange(&state, &subject_token, &params).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_target");
+    }
+
+    #[tokio::test]
+    async fn scope_narrowed() {
+        let state = state(&[("CLIENTS__WEB__TOKEN_EXCHANGE_IMPERSONATION", "true")]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = exchange(&state, &subject_token, &[("scope", "email")]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.json()["scope"], json!(["Email"]));
+    }
+
+    #[tokio::test]
+    async fn scope_not_widened() {
+        let state = state(&[("CLIENTS__WEB__TOKEN_EXCHANGE_IMPERSONATION", "true")]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = exchange(&state, &subject_token, &[("scope", "profile")]).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_scope");
+    }
+
+    #[tokio::test]
+    async fn resource_lifetime_and_scopes() {
+        let state = state(&[
+            ("CLIENTS__WEB__TOKEN_EXCHANGE_IMPERSONATION", "true"),
+            ("RESOURCES__API__EXPIRATION", "60"),
+            ("RESOURCES__API__SCOPES", "openid"),
+            ("RESOURCES__API__URI", AUDIENCE),
+        ]);
+        let subject_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = exchange(&state, &subject_token, &[]).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert_eq!(response.json()["expires_in"], 60);
+        assert_eq!(response.json()["scope"], json!(["Openid"]));
+    }
+}
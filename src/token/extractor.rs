@@ -1,26 +1,159 @@
-use crate::bounded_string::SecureString;
+use super::exchange::TokenTypeIdentifier;
+use crate::authorize::extractor::resource;
+use crate::bounded_string::{NonEmptyString, SecureString};
 use crate::data::pkce::CodeVerifier;
-use crate::data::GrantType;
-use axum::extract::{Form, FromRequest};
+use crate::data::resource::ResourceForm;
+use crate::data::Scope;
+use crate::state::client::GrantType;
+use axum::extract::FromRequest;
 use axum::http::Uri;
 use serde::{Deserialize, Deserializer};
+use serde_with::formats::SpaceSeparator;
+use serde_with::{DeserializeAs, StringWithSeparator};
+use std::collections::HashSet;
 
 #[derive(Deserialize, FromRequest)]
-#[from_request(via(Form))]
-#[allow(unused)]
-pub struct TokenParams {
+#[from_request(via(ResourceForm))]
+#[serde(tag = "grant_type")]
+pub enum TokenParams {
+    #[serde(rename = "authorization_code")]
+    AuthorizationCode(AuthorizationCodeParams),
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode(DeviceCodeParams),
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:token-exchange")]
+    TokenExchange(TokenExchangeParams),
+    #[serde(rename = "password")]
+    Password(PasswordParams),
+    #[serde(rename = "urn:openid:params:grant-type:ciba")]
+    Ciba(CibaParams),
+}
+
+impl TokenParams {
+    pub fn client_id(&self) -> &SecureString {
+        match self {
+            Self::AuthorizationCode(params) => &params.client_id,
+            Self::DeviceCode(params) => &params.client_id,
+            Self::TokenExchange(params) => &params.client_id,
+            Self::Password(params) => &params.client_id,
+            Self::Ciba(params) => &params.client_id,
+        }
+    }
+
+    pub fn grant_type(&self) -> GrantType {
+        match self {
+            Self::AuthorizationCode(_) => GrantType::AuthorizationCode,
+            Self::DeviceCode(_) => GrantType::DeviceCode,
+            Self::TokenExchange(_) => GrantType::TokenExchange,
+            Self::Password(_) => GrantType::Password,
+            Self::Ciba(_) => GrantType::Ciba,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AuthorizationCodeParams {
     #[serde(deserialize_with = "client_id")]
     pub client_id: SecureString,
-    #[serde(deserialize_with = "client_secret")]
-    pub client_secret: SecureString,
+    #[serde(default, deserialize_with = "optional_client_secret")]
+    pub client_secret: Option<SecureString>,
     #[serde(deserialize_with = "code_verifier")]
     pub code_verifier: CodeVerifier,
     #[serde(deserialize_with = "code")]
     pub code: SecureString,
-    #[serde(deserialize_with = "grant_type")]
-    pub grant_type: GrantType,
     #[serde(deserialize_with = "redirect_uri")]
     pub redirect_uri: Uri,
+    #[serde(default, deserialize_with = "resource")]
+    pub resource: Vec<Uri>,
+}
+
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#token_request
+#[derive(Deserialize)]
+pub struct CibaParams {
+    #[serde(deserialize_with = "auth_req_id")]
+    pub auth_req_id: SecureString,
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(default, deserialize_with = "optional_client_secret")]
+    pub client_secret: Option<SecureString>,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceCodeParams {
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(default, deserialize_with = "optional_client_secret")]
+    pub client_secret: Option<SecureString>,
+    #[serde(deserialize_with = "device_code")]
+    pub device_code: SecureString,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-4.3.2
+#[derive(Deserialize)]
+pub struct PasswordParams {
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(default, deserialize_with = "optional_client_secret")]
+    pub client_secret: Option<SecureString>,
+    #[serde(deserialize_with = "password")]
+    pub password: NonEmptyString,
+    #[serde(default, deserialize_with = "resource")]
+    pub resource: Vec<Uri>,
+    #[serde(default, deserialize_with = "scope")]
+    pub scope: Option<HashSet<Scope>>,
+    #[serde(deserialize_with = "username")]
+    pub username: NonEmptyString,
+}
+
+// https://datatracker.ietf.org/doc/html/rfc8693#section-2.1
+#[derive(Deserialize)]
+pub struct TokenExchangeParams {
+    #[serde(default, deserialize_with = "actor_token")]
+    pub actor_token: Option<SecureString>,
+    #[serde(default, deserialize_with = "actor_token_type")]
+    pub actor_token_type: Option<TokenTypeIdentifier>,
+    #[serde(default, deserialize_with = "audience")]
+    pub audience: Option<Uri>,
+    #[serde(deserialize_with = "client_id")]
+    pub client_id: SecureString,
+    #[serde(default, deserialize_with = "optional_client_secret")]
+    pub client_secret: Option<SecureString>,
+    #[serde(default, deserialize_with = "requested_token_type")]
+    pub requested_token_type: Option<TokenTypeIdentifier>,
+    #[serde(default, deserialize_with = "scope")]
+    pub scope: Option<HashSet<Scope>>,
+    #[serde(deserialize_with = "subject_token")]
+    pub subject_token: SecureString,
+    #[serde(deserialize_with = "subject_token_type")]
+    pub subject_token_type: TokenTypeIdentifier,
+}
+
+fn actor_token<'de, D: Deserializer<'de>>(d: D) -> Result<Option<SecureString>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `actor_token`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn actor_token_type<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<TokenTypeIdentifier>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `actor_token_type`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn audience<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Uri>, D::Error> {
+    http_serde::uri::deserialize(d)
+        .map_err(|e| format!("error while parsing field `audience`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn auth_req_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `auth_req_id`: {e}"))
+        .map_err(serde::de::Error::custom)
 }
 
 fn client_id<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
@@ -35,6 +168,12 @@ fn client_secret<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Err
         .map_err(serde::de::Error::custom)
 }
 
+fn optional_client_secret<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<SecureString>, D::Error> {
+    client_secret(d).map(Some)
+}
+
 fn code<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
     Deserialize::deserialize(d)
         .map_err(|e| format!("error while parsing field `code`: {e}"))
@@ -47,9 +186,15 @@ fn code_verifier<'de, D: Deserializer<'de>>(d: D) -> Result<CodeVerifier, D::Err
         .map_err(serde::de::Error::custom)
 }
 
-fn grant_type<'de, D: Deserializer<'de>>(d: D) -> Result<GrantType, D::Error> {
+fn device_code<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
     Deserialize::deserialize(d)
-        .map_err(|e| format!("error while parsing field `grant_type`: {e}"))
+        .map_err(|e| format!("error while parsing field `device_code`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn password<'de, D: Deserializer<'de>>(d: D) -> Result<NonEmptyString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `password`: {e}"))
         .map_err(serde::de::Error::custom)
 }
 
@@ -58,3 +203,37 @@ fn redirect_uri<'de, D: Deserializer<'de>>(d: D) -> Result<Uri, D::Error> {
         .map_err(|e| format!("error while parsing field `redirect_uri`: {e}"))
         .map_err(serde::de::Error::custom)
 }
+
+fn requested_token_type<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Option<TokenTypeIdentifier>, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `requested_token_type`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn scope<'de, D: Deserializer<'de>>(d: D) -> Result<Option<HashSet<Scope>>, D::Error> {
+    StringWithSeparator::<SpaceSeparator, Scope>::deserialize_as(d)
+        .map_err(|e| format!("error while parsing field `scope`: {e}"))
+        .map_err(serde::de::Error::custom)
+        .map(Some)
+}
+
+fn subject_token<'de, D: Deserializer<'de>>(d: D) -> Result<SecureString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `subject_token`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn subject_token_type<'de, D: Deserializer<'de>>(d: D) -> Result<TokenTypeIdentifier, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `subject_token_type`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
+
+fn username<'de, D: Deserializer<'de>>(d: D) -> Result<NonEmptyString, D::Error> {
+    Deserialize::deserialize(d)
+        .map_err(|e| format!("error while parsing field `username`: {e}"))
+        .map_err(serde::de::Error::custom)
+}
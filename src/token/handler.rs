@@ -1,24 +1,28 @@
-use super::extractor::TokenParams;
-use super::jwt;
+use super::extractor::{AuthorizationCodeParams, CibaParams, DeviceCodeParams, TokenParams};
+use super::{exchange, issue, password};
 use crate::bounded_string::SecureString;
-use crate::data::access_token::AccessToken;
+use crate::data::access_token::Confirmation;
+use crate::data::authorization_details::AuthorizationDetail;
 use crate::data::pkce::{CodeChallenge, CodeVerifier};
-use crate::data::time::UnixTime;
-use crate::data::{pkce, AuthenticationMethod, IdToken, Scope, TokenType};
-use crate::state::{AppState, AuthSession};
+use crate::data::{pkce, ErrorResponse, Scope, TokenType};
+use crate::dpop::{self, DpopRequest};
+use crate::mtls::{self, ClientCertificate};
+use crate::state::client::{Client, GrantType, TokenEndpointAuthMethod};
+use crate::state::device::PollError;
+use crate::state::{AppState, AuthSession, Grant};
 use axum::http::{StatusCode, Uri};
 use axum::response::{IntoResponse, Response, Result};
-use axum::Json;
-use base64::prelude::BASE64_URL_SAFE_NO_PAD;
-use base64::Engine;
+use axum::{Extension, Json};
 use serde::Serialize;
-use sha2::{Digest, Sha256};
 use std::collections::HashSet;
 use thiserror::Error;
 
 #[derive(Serialize)]
 pub struct TokenResponse {
     access_token: String,
+    // https://datatracker.ietf.org/doc/html/rfc9396#section-7
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    authorization_details: Vec<AuthorizationDetail>,
     expires_in: u64,
     id_token: String,
     scope: HashSet<Scope>,
@@ -27,68 +31,256 @@ pub struct TokenResponse {
 
 #[derive(Debug, Error)]
 pub enum InvalidParamError {
-    #[error("`client_secret` does not match: expected `{0}` got `{0}`")]
-    ClientSecret(SecureString, SecureString),
+    #[error("`client_secret` does not match")]
+    ClientSecret,
+    #[error("missing field `client_secret`")]
+    MissingClientSecret,
+    #[error("client `{0}` has been deleted")]
+    DeletedClient(SecureString),
+    #[error("client `{0}` is not registered for the `{1}` grant type")]
+    UnauthorizedClient(SecureString, GrantType),
     #[error("no auth session associated with code `{0}`")]
     Code(SecureString),
+    #[error("code was not issued to client `{0}`")]
+    CodeClient(SecureString),
     #[error("pkce verification failed: expected `{0}` got `{1}`")]
     Grant(CodeChallenge, CodeVerifier),
     #[error("`redirect_uri` does not match: expected `{0}` got `{1}`")]
-    RedirectUri(Uri, Uri),
-    #[error("jwt encode error: `{0}`")]
-    Jwt(#[from] jsonwebtoken::errors::Error),
+    RedirectUri(Box<Uri>, Box<Uri>),
+    #[error("resource `{0}` is unknown or was not authorized")]
+    Resource(Uri),
+    #[error(transparent)]
+    Issue(#[from] issue::Error),
+    #[error(transparent)]
+    Device(#[from] PollError),
+    #[error(transparent)]
+    Exchange(#[from] exchange::Error),
+    #[error(transparent)]
+    Password(#[from] password::Error),
+    #[error(transparent)]
+    Dpop(#[from] dpop::Error),
+    #[error(transparent)]
+    Mtls(#[from] mtls::Error),
 }
 
 impl IntoResponse for InvalidParamError {
     fn into_response(self) -> Response {
         let response = match self {
             Self::Code(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            // https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+            // https://datatracker.ietf.org/doc/html/rfc8705#section-2
+            Self::ClientSecret
+            | Self::MissingClientSecret
+            | Self::DeletedClient(_)
+            | Self::Mtls(_) => {
+                let response = ErrorResponse::new("invalid_client", &self);
+                return (StatusCode::UNAUTHORIZED, Json(response)).into_response();
+            }
             Self::Grant { .. } => (StatusCode::BAD_REQUEST, self.to_string()),
+            // https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.3
+            Self::CodeClient(_) => {
+                let response = ErrorResponse::new("invalid_grant", &self);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
+            // https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+            Self::UnauthorizedClient(..) => {
+                let response = ErrorResponse::new("unauthorized_client", &self);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
+            Self::Resource(_) => {
+                let response = ErrorResponse::new("invalid_target", &self);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
+            Self::Device(ref e) => {
+                let response = ErrorResponse::new(e.code(), e);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
+            Self::Exchange(exchange::Error::Jwt(_)) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
+            }
+            Self::Dpop(e) => return e.token_response(),
+            Self::Password(ref e) => {
+                let response = ErrorResponse::new(e.code(), e);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
+            Self::Exchange(ref e) => {
+                let response = ErrorResponse::new(e.code(), e);
+                return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+            }
             _ => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
         };
         response.into_response()
     }
 }
 
-pub async fn token(state: AppState, params: TokenParams) -> Result<impl IntoResponse> {
-    let auth_session = get_session(&state, params.code)?;
-    verify_pkce(auth_session.code_challenge, params.code_verifier)?;
-    verify_redirect_uri(auth_session.redirect_uri, params.redirect_uri)?;
-    verify_client_secret(params.client_secret, state.client_secret())?;
-    let now = UnixTime::now();
-    let access_token = AccessToken {
-        aud: state.audience(),
-        auth_time: now,
-        cid: auth_session.client_id.clone(),
-        exp: now + state.expiration(),
-        iat: now,
-        iss: state.issuer(),
-        jti: state.gen_secure_string(),
-        scp: auth_session.scope.clone(),
-        sub: auth_session.user_id.clone(),
-        uid: auth_session.user_id.clone(),
-        ver: 1,
+pub async fn token(
+    state: AppState,
+    dpop: DpopRequest,
+    certificate: Option<Extension<ClientCertificate>>,
+    params: TokenParams,
+) -> Result<Response> {
+    // The proof is checked first so that a nonce challenge does not consume the grant
+    // https://datatracker.ietf.org/doc/html/rfc9449#section-5
+    let jkt = dpop.verify(&state, None).map_err(InvalidParamError::from)?;
+    let token_type = match jkt {
+        Some(_) => TokenType::Dpop,
+        None => TokenType::Bearer,
     };
-    let access_token = jwt::encode(access_token, state.secret()).unwrap();
-    let id_token = IdToken {
-        amr: vec![AuthenticationMethod::Pwd],
-        at_hash: access_token_hash(&access_token),
-        aud: auth_session.client_id,
-        auth_time: now,
-        exp: now + state.expiration(),
-        iat: now,
-        iss: state.issuer(),
-        jti: state.gen_secure_string(),
-        sub: auth_session.user_id,
-        ver: 1,
+    let client_id = params.client_id();
+    if !state
+        .client(client_id)
+        .allows_grant_type(params.grant_type())
+    {
+        let e = InvalidParamError::UnauthorizedClient(client_id.clone(), params.grant_type());
+        return Err(e.into());
+    }
+    let certificate = certificate
+        .as_ref()
+        .map(|Extension(certificate)| certificate);
+    let grant = match params {
+        TokenParams::AuthorizationCode(params) => authorization_code(&state, params, certificate)?,
+        TokenParams::DeviceCode(params) => device_code(&state, params, certificate)?,
+        TokenParams::Ciba(params) => ciba(&state, params, certificate)?,
+        TokenParams::Password(params) => {
+            let client = authenticate_client(
+                &state,
+                &params.client_id,
+                params.client_secret.clone(),
+                certificate,
+            )?;
+            verify_resource(&state, &[], &params.resource)?;
+            password::grant(&state, client, params).map_err(InvalidParamError::from)?
+        }
+        TokenParams::TokenExchange(params) => {
+            let client = authenticate_client(
+                &state,
+                &params.client_id,
+                params.client_secret.clone(),
+                certificate,
+            )?;
+            let cnf = confirmation(&client, jkt, certificate);
+            let response =
+                exchange::exchange(&state, client, params, cnf).map_err(InvalidParamError::from)?;
+            return Ok(Json(response).into_response());
+        }
     };
+    let cnf = confirmation(&state.client(&grant.client_id), jkt, certificate);
+    let audience = state.token_audience(&grant.resource);
+    let access_token =
+        issue::access_token(&state, &grant, &audience, cnf).map_err(InvalidParamError::from)?;
+    let id_token = issue::id_token(&state, &grant, Some(&access_token), None)
+        .map_err(InvalidParamError::from)?;
     Ok(Json(TokenResponse {
         access_token,
-        expires_in: state.expiration(),
-        id_token: jwt::encode(id_token, state.secret()).unwrap(),
-        scope: auth_session.scope,
-        token_type: TokenType::Bearer,
-    }))
+        authorization_details: grant.authorization_details,
+        expires_in: audience.expiration,
+        id_token,
+        scope: audience.scope(&grant.scope),
+        token_type,
+    })
+    .into_response())
+}
+
+fn authorization_code(
+    state: &AppState,
+    params: AuthorizationCodeParams,
+    certificate: Option<&ClientCertificate>,
+) -> Result<Grant, InvalidParamError> {
+    let auth_session = get_session(state, params.code)?;
+    if auth_session.grant.client_id != params.client_id {
+        return Err(InvalidParamError::CodeClient(params.client_id));
+    }
+    verify_pkce(auth_session.code_challenge, params.code_verifier)?;
+    verify_redirect_uri(auth_session.redirect_uri, params.redirect_uri)?;
+    authenticate_client(state, &params.client_id, params.client_secret, certificate)?;
+    let mut grant = auth_session.grant;
+    // https://datatracker.ietf.org/doc/html/rfc8707#section-2.2
+    if !params.resource.is_empty() {
+        verify_resource(state, &grant.resource, &params.resource)?;
+        grant.resource = params.resource;
+    }
+    Ok(grant)
+}
+
+fn device_code(
+    state: &AppState,
+    params: DeviceCodeParams,
+    certificate: Option<&ClientCertificate>,
+) -> Result<Grant, InvalidParamError> {
+    authenticate_device_client(state, &params.client_id, params.client_secret, certificate)?;
+    Ok(state.poll_device_session(&params.device_code, &params.client_id)?)
+}
+
+// Only the client that made the request may redeem it
+// https://openid.net/specs/openid-client-initiated-backchannel-authentication-core-1_0.html#token_request
+fn ciba(
+    state: &AppState,
+    params: CibaParams,
+    certificate: Option<&ClientCertificate>,
+) -> Result<Grant, InvalidParamError> {
+    authenticate_client(state, &params.client_id, params.client_secret, certificate)?;
+    Ok(state.poll_backchannel_request(&params.auth_req_id, &params.client_id)?)
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-2.3
+pub fn authenticate_client(
+    state: &AppState,
+    client_id: &SecureString,
+    client_secret: Option<SecureString>,
+    certificate: Option<&ClientCertificate>,
+) -> Result<Client, InvalidParamError> {
+    // Deleted clients do not fall back to unregistered clients
+    if state.is_deleted_client(client_id) {
+        return Err(InvalidParamError::DeletedClient(client_id.clone()));
+    }
+    let client = state.client(client_id);
+    match client.token_endpoint_auth_method {
+        TokenEndpointAuthMethod::ClientSecretPost => {
+            let client_secret = client_secret.ok_or(InvalidParamError::MissingClientSecret)?;
+            verify_client_secret(state.client_secret(client_id), client_secret)?;
+        }
+        TokenEndpointAuthMethod::TlsClientAuth => {
+            mtls::tls_client_auth(&client, certificate)?;
+        }
+        TokenEndpointAuthMethod::SelfSignedTlsClientAuth => {
+            mtls::self_signed_tls_client_auth(&client, certificate)?;
+        }
+    }
+    Ok(client)
+}
+
+// Only the public clients, which have no credentials, may omit them
+// https://datatracker.ietf.org/doc/html/rfc8628#section-3.4
+pub fn authenticate_device_client(
+    state: &AppState,
+    client_id: &SecureString,
+    client_secret: Option<SecureString>,
+    certificate: Option<&ClientCertificate>,
+) -> Result<Client, InvalidParamError> {
+    let client = state.client(client_id);
+    if !state.is_deleted_client(client_id)
+        && state.client_secret(client_id).is_none()
+        && client.token_endpoint_auth_method == TokenEndpointAuthMethod::ClientSecretPost
+    {
+        return Ok(client);
+    }
+    authenticate_client(state, client_id, client_secret, certificate)
+}
+
+// The keys the issued access token is bound to
+fn confirmation(
+    client: &Client,
+    jkt: Option<String>,
+    certificate: Option<&ClientCertificate>,
+) -> Option<Confirmation> {
+    // https://datatracker.ietf.org/doc/html/rfc8705#section-3
+    let x5t_s256 = certificate
+        .filter(|_| client.tls_client_certificate_bound_access_tokens)
+        .map(ClientCertificate::thumbprint);
+    match (jkt, x5t_s256) {
+        (None, None) => None,
+        (jkt, x5t_s256) => Some(Confirmation { jkt, x5t_s256 }),
+    }
 }
 
 fn get_session(state: &AppState, code: SecureString) -> Result<AuthSession, InvalidParamError> {
@@ -109,24 +301,99 @@ fn verify_redirect_uri(expected: Uri, got: Uri) -> Result<(), InvalidParamError>
     if expected == got {
         Ok(())
     } else {
-        Err(InvalidParamError::RedirectUri(expected, got))
+        Err(InvalidParamError::RedirectUri(
+            Box::new(expected),
+            Box::new(got),
+        ))
+    }
+}
+
+// Without resources in the authorization request any known resource can be requested
+fn verify_resource(
+    state: &AppState,
+    authorized: &[Uri],
+    requested: &[Uri],
+) -> Result<(), InvalidParamError> {
+    let allowed = |uri: &Uri| match authorized {
+        [] => state.is_resource(uri),
+        _ => authorized.contains(uri),
+    };
+    match requested.iter().find(|uri| !allowed(uri)) {
+        Some(uri) => Err(InvalidParamError::Resource(uri.clone())),
+        None => Ok(()),
     }
 }
 
 fn verify_client_secret(
-    expected: SecureString,
+    expected: Option<SecureString>,
     got: SecureString,
 ) -> Result<(), InvalidParamError> {
-    if expected == got {
+    if expected == Some(got) {
         Ok(())
     } else {
-        Err(InvalidParamError::ClientSecret(expected, got))
+        Err(InvalidParamError::ClientSecret)
     }
 }
 
-fn access_token_hash(access_token: &String) -> String {
-    let hash = Sha256::digest(access_token);
-    let half_length = hash.len() / 2;
-    let half_hash = &hash[..half_length];
-    BASE64_URL_SAFE_NO_PAD.encode(half_hash)
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET, CODE_VERIFIER, REDIRECT_URI};
+    use axum::http::StatusCode;
+
+    async fn redeem(
+        state: &crate::state::AppState,
+        client_id: &str,
+        code: &str,
+    ) -> testing::TestResponse {
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", client_id),
+            ("client_secret", CLIENT_SECRET),
+            ("code", code),
+            ("code_verifier", CODE_VERIFIER),
+            ("redirect_uri", REDIRECT_URI),
+        ];
+        testing::post_form(state, "/token", &params).await
+    }
+
+    #[tokio::test]
+    async fn code_redeemed_by_its_client() {
+        let state = testing::state(&[]);
+        let code = testing::authorization_code(&state, CLIENT_ID).await;
+        let response = redeem(&state, CLIENT_ID, &code).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.json()["access_token"].is_string());
+        assert!(response.json()["id_token"].is_string());
+    }
+
+    #[tokio::test]
+    async fn code_bound_to_its_client() {
+        let state = testing::state(&[]);
+        let code = testing::authorization_code(&state, CLIENT_ID).await;
+        let response = redeem(&state, "otherClientIdentifier", &code).await;
+        assert_eq!(
+            response.status,
+            StatusCode::BAD_REQUEST,
+            "{}",
+            response.body
+        );
+        assert_eq!(response.json()["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn client_secret_not_disclosed() {
+        let state = testing::state(&[]);
+        let code = testing::authorization_code(&state, CLIENT_ID).await;
+        let params = [
+            ("grant_type", "authorization_code"),
+            ("client_id", CLIENT_ID),
+            ("client_secret", "0000000000000000000000000000000000000000"),
+            ("code", &code),
+            ("code_verifier", CODE_VERIFIER),
+            ("redirect_uri", REDIRECT_URI),
+        ];
+        let response = testing::post_form(&state, "/token", &params).await;
+        assert!(!response.status.is_success());
+        assert!(!response.body.contains(CLIENT_SECRET));
+    }
 }
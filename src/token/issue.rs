@@ -0,0 +1,125 @@
+use super::jwt;
+use crate::bounded_string::SecureString;
+use crate::crypto::{half_hash, jwe};
+use crate::data::access_token::{AccessToken, AccessTokenFormat, Confirmation};
+use crate::data::id_token::IdToken;
+use crate::data::time::UnixTime;
+use crate::state::resource::Audience;
+use crate::state::{AppState, Grant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("jwt encode error: `{0}`")]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("jwe encode error: `{0}`")]
+    Jwe(#[from] jwe::Error),
+}
+
+pub fn access_token(
+    state: &AppState,
+    grant: &Grant,
+    audience: &Audience,
+    cnf: Option<Confirmation>,
+) -> Result<String, Error> {
+    let now = UnixTime::now();
+    let access_token = AccessToken {
+        acr: grant.authentication.acr.clone(),
+        act: None,
+        aud: audience.aud.clone(),
+        auth_time: grant.auth_time,
+        authorization_details: grant.authorization_details.clone(),
+        cid: grant.client_id.clone(),
+        cnf,
+        exp: now + audience.expiration,
+        iat: now,
+        iss: state.issuer(),
+        jti: state.gen_secure_string(),
+        scp: audience.scope(&grant.scope),
+        sub: grant.user_id.clone(),
+        uid: grant.user_id.clone(),
+        ver: 1,
+    };
+    Ok(encode_access_token(state, access_token)?)
+}
+
+// Opaque access tokens are random references to the claims kept by the server
+pub fn encode_access_token(
+    state: &AppState,
+    access_token: AccessToken,
+) -> jsonwebtoken::errors::Result<String> {
+    match state.access_token_format(&access_token.cid) {
+        AccessTokenFormat::Jwt => {
+            let profile = state.access_token_profile(&access_token.cid);
+            jwt::encode_access_token(access_token, profile, state.signing_key())
+        }
+        AccessTokenFormat::Opaque => {
+            let reference = state.gen_secure_string();
+            state.set_access_token(reference.clone(), access_token);
+            Ok(reference.to_string())
+        }
+    }
+}
+
+pub fn id_token(
+    state: &AppState,
+    grant: &Grant,
+    access_token: Option<&str>,
+    code: Option<&SecureString>,
+) -> Result<String, Error> {
+    let now = UnixTime::now();
+    let id_token = IdToken {
+        acr: grant.authentication.acr.clone(),
+        amr: grant.authentication.amr.clone(),
+        at_hash: access_token.map(half_hash),
+        aud: grant.client_id.clone(),
+        auth_time: grant.auth_time,
+        c_hash: code.map(|c| half_hash(c.as_ref())),
+        exp: now + state.expiration(),
+        iat: now,
+        iss: state.issuer(),
+        jti: state.gen_secure_string(),
+        nonce: grant.nonce.clone(),
+        // Sessions are always supported so the `sid` claim is always included
+        // https://openid.net/specs/openid-connect-backchannel-1_0.html#Indicator
+        sid: grant.sid.clone(),
+        sub: grant.user_id.clone(),
+        ver: 1,
+    };
+    let id_token = jwt::encode(id_token, state.signing_key())?;
+    // https://openid.net/specs/openid-connect-core-1_0.html#Encryption
+    let client = state.client(&grant.client_id);
+    match client.id_token_encrypted_response_alg {
+        Some(alg) => {
+            let enc = client.id_token_encrypted_response_enc;
+            Ok(client.encrypt(id_token.as_bytes(), alg, enc, Some("JWT"))?)
+        }
+        None => Ok(id_token),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID};
+
+    #[tokio::test]
+    async fn opaque_access_token() {
+        let state = testing::state(&[("ACCESS_TOKEN_FORMAT", "opaque")]);
+        let access_token = testing::access_token(&state, CLIENT_ID).await;
+        assert!(!access_token.contains('.'));
+        assert!(state.access_token(&access_token).is_some());
+    }
+
+    #[tokio::test]
+    async fn opaque_access_token_of_the_client() {
+        let state = testing::state(&[
+            ("CLIENTS__WEB__ACCESS_TOKEN_FORMAT", "opaque"),
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+        ]);
+        let access_token = testing::access_token(&state, CLIENT_ID).await;
+        assert!(state.access_token(&access_token).is_some());
+        let other = "otherClientIdentifier";
+        let access_token = testing::access_token(&state, other).await;
+        assert_eq!(access_token.split('.').count(), 3);
+    }
+}
@@ -1,10 +1,84 @@
-use crate::data::Secret;
+use crate::data::access_token::{
+    AccessToken, AccessTokenProfile, ProfiledAccessToken, Rfc9068AccessToken,
+};
+use crate::state::keys::SigningKey;
+use crate::state::AppState;
 use jsonwebtoken::errors::Result;
-use jsonwebtoken::{EncodingKey, Header};
+use jsonwebtoken::{decode_header, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::de::DeserializeOwned;
 use serde::Serialize;
+use thiserror::Error;
 
-pub fn encode<C: Serialize>(claims: C, secret: Secret) -> Result<String> {
-    let header = &Header::default();
-    let key = EncodingKey::from_secret(secret.as_ref());
-    jsonwebtoken::encode(header, &claims, &key)
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    #[error(transparent)]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("signed with an unknown or retired key `{0}`")]
+    UnknownKey(String),
+}
+
+pub fn encode<C: Serialize>(claims: C, key: SigningKey) -> Result<String> {
+    sign(claims, key, Algorithm::HS256)
+}
+
+pub fn sign<C: Serialize>(claims: C, key: SigningKey, algorithm: Algorithm) -> Result<String> {
+    sign_with_header(Header::new(algorithm), claims, key)
+}
+
+fn sign_with_header<C: Serialize>(
+    mut header: Header,
+    claims: C,
+    key: SigningKey,
+) -> Result<String> {
+    header.kid = Some(key.kid);
+    let key = EncodingKey::from_secret(key.secret.as_ref());
+    jsonwebtoken::encode(&header, &claims, &key)
+}
+
+pub fn encode_access_token(
+    access_token: AccessToken,
+    profile: AccessTokenProfile,
+    key: SigningKey,
+) -> Result<String> {
+    match profile {
+        AccessTokenProfile::Okta => encode(access_token, key),
+        // https://datatracker.ietf.org/doc/html/rfc9068#section-2.1
+        AccessTokenProfile::Rfc9068 => {
+            let mut header = Header::new(Algorithm::HS256);
+            header.typ = Some("at+jwt".to_string());
+            sign_with_header(header, Rfc9068AccessToken::from(access_token), key)
+        }
+    }
+}
+
+// https://openid.net/specs/openid-connect-backchannel-1_0.html#LogoutToken
+pub fn encode_logout_token<C: Serialize>(logout_token: C, key: SigningKey) -> Result<String> {
+    let mut header = Header::new(Algorithm::HS256);
+    header.typ = Some("logout+jwt".to_string());
+    sign_with_header(header, logout_token, key)
+}
+
+pub fn decode<C: DeserializeOwned>(
+    state: &AppState,
+    token: &str,
+    validation: &Validation,
+) -> std::result::Result<C, DecodeError> {
+    // Tokens issued before the key rotation support carry no key identifier
+    let key = match decode_header(token)?.kid {
+        Some(kid) => state
+            .verification_key(&kid)
+            .ok_or(DecodeError::UnknownKey(kid))?,
+        None => state.signing_key(),
+    };
+    let key = DecodingKey::from_secret(key.secret.as_ref());
+    Ok(jsonwebtoken::decode(token, &key, validation)?.claims)
+}
+
+// Access tokens are accepted in either profile
+pub fn decode_access_token(
+    state: &AppState,
+    token: &str,
+    validation: &Validation,
+) -> std::result::Result<AccessToken, DecodeError> {
+    decode::<ProfiledAccessToken>(state, token, validation).map(AccessToken::from)
 }
@@ -1,3 +1,6 @@
+pub mod exchange;
 pub mod extractor;
 pub mod handler;
-mod jwt;
+pub mod issue;
+pub mod jwt;
+pub mod password;
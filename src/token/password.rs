@@ -0,0 +1,143 @@
+use super::extractor::PasswordParams;
+use crate::bounded_string::SecureString;
+use crate::data::time::UnixTime;
+use crate::data::Scope;
+use crate::state::client::Client;
+use crate::state::{AppState, Grant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("client `{0}` may not use the password grant")]
+    UnauthorizedClient(SecureString),
+    #[error("invalid username or password")]
+    InvalidCredentials,
+    #[error("the user must complete a second factor that the password grant cannot ask for")]
+    SecondFactor,
+}
+
+impl Error {
+    // https://datatracker.ietf.org/doc/html/rfc6749#section-5.2
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnauthorizedClient(_) => "unauthorized_client",
+            Self::InvalidCredentials | Self::SecondFactor => "invalid_grant",
+        }
+    }
+}
+
+// https://datatracker.ietf.org/doc/html/rfc6749#section-4.3.2
+pub fn grant(state: &AppState, client: Client, params: PasswordParams) -> Result<Grant, Error> {
+    if !client.password_grant {
+        return Err(Error::UnauthorizedClient(client.client_id));
+    }
+    let user = state.get_user();
+    if !user.verify_password(&params.username, &params.password) {
+        return Err(Error::InvalidCredentials);
+    }
+    if user.totp_secret.is_some() {
+        return Err(Error::SecondFactor);
+    }
+    Ok(Grant {
+        auth_time: UnixTime::now(),
+        authentication: state.authentication(),
+        authorization_details: Vec::new(),
+        client_id: client.client_id,
+        nonce: None,
+        resource: params.resource,
+        // Without a scope the client gets every scope
+        scope: params.scope.unwrap_or_else(Scope::all),
+        sid: None,
+        user_id: state.get_user().id(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::state::AppState;
+    use crate::testing::{self, CLIENT_ID, CLIENT_SECRET};
+    use axum::http::StatusCode;
+
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn state(overrides: &[(&str, &str)]) -> AppState {
+        let defaults = [
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__PASSWORD_GRANT", "true"),
+            ("USER__PASSWORD", PASSWORD),
+        ];
+        testing::state_with(&defaults, overrides)
+    }
+
+    async fn token(
+        state: &AppState,
+        client_id: &str,
+        username: &str,
+        password: &str,
+    ) -> testing::TestResponse {
+        let params = [
+            ("grant_type", "password"),
+            ("client_id", client_id),
+            ("client_secret", CLIENT_SECRET),
+            ("username", username),
+            ("password", password),
+            ("scope", "openid email"),
+        ];
+        testing::post_form(state, "/token", &params).await
+    }
+
+    #[tokio::test]
+    async fn password_grant() {
+        let state = state(&[]);
+        for username in ["johnny", "john.doe@example.com"] {
+            let response = token(&state, CLIENT_ID, username, PASSWORD).await;
+            assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+            assert!(response.json()["access_token"].is_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn client_not_opted_in() {
+        let state = state(&[]);
+        let response = token(&state, "otherClientIdentifier", "johnny", PASSWORD).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "unauthorized_client");
+    }
+
+    #[tokio::test]
+    async fn client_opted_out() {
+        let state = state(&[("CLIENTS__WEB__PASSWORD_GRANT", "false")]);
+        let response = token(&state, CLIENT_ID, "johnny", PASSWORD).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "unauthorized_client");
+    }
+
+    #[tokio::test]
+    async fn invalid_credentials() {
+        let state = state(&[]);
+        for (username, password) in [("johnny", "wrong password"), ("jane", PASSWORD)] {
+            let response = token(&state, CLIENT_ID, username, password).await;
+            assert_eq!(response.status, StatusCode::BAD_REQUEST);
+            assert_eq!(response.json()["error"], "invalid_grant");
+        }
+    }
+
+    #[tokio::test]
+    async fn without_a_password() {
+        let state = testing::state(&[
+            ("CLIENTS__WEB__CLIENT_ID", CLIENT_ID),
+            ("CLIENTS__WEB__PASSWORD_GRANT", "true"),
+        ]);
+        let response = token(&state, CLIENT_ID, "johnny", PASSWORD).await;
+        assert_eq!(response.json()["error"], "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn second_factor_refused() {
+        let state = state(&[("USER__TOTP_SECRET", "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")]);
+        let response = token(&state, CLIENT_ID, "johnny", PASSWORD).await;
+        assert_eq!(response.status, StatusCode::BAD_REQUEST);
+        assert_eq!(response.json()["error"], "invalid_grant");
+        assert!(response.body.contains("second factor"));
+    }
+}
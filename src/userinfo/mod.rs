@@ -1,46 +1,170 @@
+use crate::bounded_string::{NonEmptyString, SecureString};
+use crate::crypto::jwe;
 use crate::data::access_token::{self, AccessToken};
-use crate::state::AppState;
-use axum::http::StatusCode;
+use crate::data::claims::AcrRequest;
+use crate::data::quoted_string;
+use crate::dpop::{self, DpopRequest, TokenAuthorization};
+use crate::mtls::{self, ClientCertificate};
+use crate::state::client::Client;
+use crate::state::{AppState, User};
+use crate::token::jwt;
+use axum::http::header::{CONTENT_TYPE, WWW_AUTHENTICATE};
+use axum::http::{StatusCode, Uri};
 use axum::response::{IntoResponse, Response, Result};
-use axum::Json;
-use axum_extra::headers::authorization::Bearer;
-use axum_extra::headers::Authorization;
-use axum_extra::TypedHeader;
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use axum::{Extension, Json};
+use jsonwebtoken::{Algorithm, Validation};
+use serde::Serialize;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("invalid access_token: {0}")]
-    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    InvalidToken(#[from] jwt::DecodeError),
+    #[error("the authentication `acr` `{0}` does not satisfy the required ones")]
+    InsufficientUserAuthentication(NonEmptyString, Vec<NonEmptyString>),
     #[error("invalid access_token claim: {0}")]
     InvalidClaim(#[from] access_token::Error),
+    #[error("jwt encode error: `{0}`")]
+    Jwt(#[source] jsonwebtoken::errors::Error),
+    #[error("jwe encode error: `{0}`")]
+    Jwe(#[from] jwe::Error),
+    #[error("json encode error: `{0}`")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Dpop(#[from] dpop::Error),
+    #[error(transparent)]
+    Mtls(#[from] mtls::Error),
+}
+
+#[derive(Serialize)]
+struct UserInfoClaims {
+    aud: SecureString,
+    #[serde(with = "http_serde::uri")]
+    iss: Uri,
+    #[serde(flatten)]
+    user: User,
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         let response = match self {
             Self::InvalidToken(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
+            // https://datatracker.ietf.org/doc/html/rfc9470#section-3
+            Self::InsufficientUserAuthentication(_, ref acr_values) => {
+                let acr_values = acr_values.iter().map(ToString::to_string);
+                let challenge = format!(
+                    r#"Bearer error="insufficient_user_authentication", error_description={}, acr_values={}"#,
+                    quoted_string(&self.to_string()),
+                    quoted_string(&acr_values.collect::<Vec<_>>().join(" "))
+                );
+                let header = [(WWW_AUTHENTICATE, challenge)];
+                return (StatusCode::UNAUTHORIZED, header, self.to_string()).into_response();
+            }
             Self::InvalidClaim(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            Self::Dpop(e) => return e.resource_response(),
+            // https://datatracker.ietf.org/doc/html/rfc8705#section-3
+            Self::Mtls(_) => {
+                let challenge = format!(
+                    r#"Bearer error="invalid_token", error_description={}"#,
+                    quoted_string(&self.to_string())
+                );
+                let header = [(WWW_AUTHENTICATE, challenge)];
+                return (StatusCode::UNAUTHORIZED, header, self.to_string()).into_response();
+            }
+            Self::Jwt(_) | Self::Jwe(_) | Self::Json(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, self.to_string())
+            }
         };
         response.into_response()
     }
 }
 
 pub async fn userinfo(
-    TypedHeader(Authorization(bearer)): TypedHeader<Authorization<Bearer>>,
+    authorization: TokenAuthorization,
+    dpop: DpopRequest,
+    certificate: Option<Extension<ClientCertificate>>,
     state: AppState,
-) -> Result<impl IntoResponse> {
-    let access_token = decode_access_token(&state, bearer)?;
+) -> Result<Response> {
+    let access_token = decode_access_token(&state, authorization.token())?;
     access_token.validate(&state).map_err(Error::InvalidClaim)?;
+    dpop.verify_binding(&state, &authorization, access_token.jkt())
+        .map_err(Error::from)?;
+    let certificate = certificate
+        .as_ref()
+        .map(|Extension(certificate)| certificate);
+    mtls::verify_binding(access_token.x5t_s256(), certificate).map_err(Error::from)?;
+    let acr_request = AcrRequest {
+        essential: true,
+        values: state.userinfo_acr_values(),
+    };
+    if !acr_request.accepts(&access_token.acr) {
+        return Err(
+            Error::InsufficientUserAuthentication(access_token.acr, acr_request.values).into(),
+        );
+    }
     let user_info = state.get_user();
-    Ok(Json(user_info))
+    // https://openid.net/specs/openid-connect-core-1_0.html#UserInfoResponse
+    let client = state.client(&access_token.cid);
+    let signed = match client.userinfo_signed_response_alg {
+        Some(alg) => Some(sign(&state, &client, user_info.clone(), alg)?),
+        None => None,
+    };
+    let response = match (signed, client.userinfo_encrypted_response_alg) {
+        (None, None) => return Ok(Json(user_info).into_response()),
+        (Some(jwt), None) => jwt,
+        (signed, Some(alg)) => {
+            let enc = client.userinfo_encrypted_response_enc;
+            let cty = signed.as_ref().map(|_| "JWT");
+            let plaintext = match signed {
+                Some(jwt) => jwt.into_bytes(),
+                None => serde_json::to_vec(&user_info).map_err(Error::from)?,
+            };
+            client
+                .encrypt(&plaintext, alg, enc, cty)
+                .map_err(Error::from)?
+        }
+    };
+    Ok(([(CONTENT_TYPE, "application/jwt")], response).into_response())
 }
 
-fn decode_access_token(state: &AppState, bearer: Bearer) -> Result<AccessToken, Error> {
+fn sign(state: &AppState, client: &Client, user: User, alg: Algorithm) -> Result<String, Error> {
+    let claims = UserInfoClaims {
+        aud: client.client_id.clone(),
+        iss: state.issuer(),
+        user,
+    };
+    jwt::sign(claims, state.signing_key(), alg).map_err(Error::Jwt)
+}
+
+fn decode_access_token(state: &AppState, token: &str) -> Result<AccessToken, Error> {
+    let opaque = state.access_token(token);
+    if let Some(access_token) = opaque.filter(|t| t.aud.contains(&state.audience())) {
+        return Ok(access_token);
+    }
     let mut validation = Validation::default();
     validation.set_audience(&[state.audience()]);
-    let key = DecodingKey::from_secret(state.secret().as_ref());
-    let token = decode(bearer.token(), &key, &validation)?;
-    Ok(token.claims)
+    Ok(jwt::decode_access_token(state, token, &validation)?)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{self, CLIENT_ID};
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn opaque_access_token() {
+        let state = testing::state(&[("ACCESS_TOKEN_FORMAT", "opaque")]);
+        let access_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = testing::userinfo(&state, &access_token).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", response.body);
+        assert!(response.json()["email"].is_string());
+    }
+
+    #[tokio::test]
+    async fn expired_opaque_access_token() {
+        let state = testing::state(&[("ACCESS_TOKEN_FORMAT", "opaque"), ("EXPIRATION", "0")]);
+        let access_token = testing::access_token(&state, CLIENT_ID).await;
+        let response = testing::userinfo(&state, &access_token).await;
+        assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    }
 }